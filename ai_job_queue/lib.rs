@@ -1,5 +1,7 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+pub use self::ai_job_queue::{AiJobQueue, AiJobQueueRef};
+
 #[ink::contract]
 mod ai_job_queue {
     use ink::prelude::string::String;
@@ -2,7 +2,9 @@
 
 #[ink::contract]
 mod compute_provider_registry {
+    use ink::env::hash::Keccak256;
     use ink::prelude::string::String;
+    use ink::prelude::vec::Vec;
     use ink::storage::Mapping;
     use ink::primitives::{H160, U256}; use ink::env::DefaultEnvironment;
 
@@ -22,11 +24,467 @@ mod compute_provider_registry {
         pub provider: H160,
         pub endpoint: String,
         pub compute_units: u64,
+        /// Legacy: the last rate set by `register_provider`, `update_provider`
+        /// or an `Hourly` pricing model. Stale under other models; read `pricing`.
         pub hourly_rate: u128,
         pub registered_at: u64,
         pub is_active: bool,
         pub stake: u128,
         pub reputation_score: u32,
+        pub capabilities: Capabilities,
+        pub jobs_completed: u64,
+        pub jobs_failed: u64,
+        pub jobs_disputed: u64,
+        pub jobs_late: u64,
+        /// Stake delegated by third parties; counts toward `min_stake`.
+        pub delegated_stake: u128,
+        /// Share of distributed rewards kept by the provider, in basis points.
+        pub commission_bps: u16,
+        /// Jobs currently running on the provider.
+        pub active_jobs: u32,
+        /// Compute units committed to running jobs, at most `compute_units`.
+        pub reserved_units: u64,
+        /// Set by the admin while the provider is under investigation; a
+        /// suspended provider cannot activate, unstake or deregister.
+        pub suspended: bool,
+        /// Block from which `complete_deregistration` may run, once requested.
+        pub deregister_at: Option<u32>,
+        /// How `quote` prices a job. Starts as `Hourly` at `hourly_rate`.
+        pub pricing: PricingModel,
+        /// Key the provider's endpoint signs challenges with.
+        pub identity_key: Option<IdentityKey>,
+        /// Whether a verifier confirmed the endpoint controls `identity_key`.
+        /// Reset whenever the endpoint or key changes.
+        pub endpoint_verified: bool,
+        /// Service level the provider commits to, if declared.
+        pub sla: Option<SlaTerms>,
+        /// Rolling share of passed liveness checks, in basis points.
+        pub uptime_bps: u32,
+        /// Number of liveness checks recorded.
+        pub liveness_checks: u64,
+    }
+
+    /// Service level a provider commits to.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct SlaTerms {
+        /// Blocks within which the provider must answer a liveness probe.
+        pub max_response_blocks: u32,
+        /// Minimum rolling uptime, in basis points.
+        pub uptime_target_bps: u16,
+    }
+
+    /// Weight of each liveness check in the rolling uptime, in basis points.
+    const UPTIME_SMOOTHING_BPS: u128 = 1_000;
+    /// Liveness checks needed before `meets_sla` can report true.
+    pub const MIN_LIVENESS_CHECKS: u64 = 10;
+
+    /// Public key bound to a provider for endpoint verification.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum IdentityKey {
+        Sr25519([u8; 32]),
+        /// Compressed secp256k1 public key.
+        Ecdsa([u8; 33]),
+    }
+
+    /// Domain separator mixed into endpoint challenges.
+    const CHALLENGE_DOMAIN: &[u8] = b"PolkaMesh:ComputeProviderRegistry:challenge";
+    /// Blocks a challenge stays answerable (~1 hour at 6s blocks).
+    pub const CHALLENGE_VALIDITY: u32 = 600;
+    /// Domain separator mixed into liveness probe nonces.
+    const PROBE_DOMAIN: &[u8] = b"PolkaMesh:ComputeProviderRegistry:probe";
+
+    /// One band of a tiered price: units up to `up_to` (inclusive, counted from
+    /// the end of the previous band) cost `price_per_unit`. `None` is unbounded.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct PriceTier {
+        pub up_to: Option<u64>,
+        pub price_per_unit: u128,
+    }
+
+    /// Pricing of a provider's compute.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum PricingModel {
+        /// `rate` per unit per started hour, billing at least `min_hours`.
+        Hourly { rate: u128, min_hours: u32 },
+        /// `price` per unit, e.g. per 1k tokens, regardless of duration.
+        PerUnit { unit: String, price: u128 },
+        /// Graduated per-unit prices; bands must have increasing `up_to` and
+        /// only the last may be unbounded.
+        Tiered(Vec<PriceTier>),
+    }
+
+    /// Maximum number of bands in a tiered price.
+    const MAX_PRICE_TIERS: usize = 8;
+    /// Seconds in a billed hour.
+    const SECONDS_PER_HOUR: u64 = 3_600;
+
+    /// A delegator's position with one provider. Shares are converted to
+    /// stake at the pool's current rate, so slashes reduce every position
+    /// pro rata.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        Default,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Delegation {
+        pub shares: u128,
+        /// `shares * reward_per_share` at the last settlement.
+        pub reward_debt: u128,
+        /// Settled rewards not yet claimed.
+        pub pending_rewards: u128,
+    }
+
+    /// Per-provider delegation pool.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        Default,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct DelegationPool {
+        pub total_shares: u128,
+        /// Rewards per share accumulated since the pool was created, scaled by `REWARD_PRECISION`.
+        pub reward_per_share: u128,
+        /// Undelegated stake still unbonding, and still slashable.
+        pub unbonding: u128,
+        /// Shares of `unbonding` held by undelegation entries.
+        pub unbonding_shares: u128,
+    }
+
+    /// A delegator's pending undelegation from one provider, held as shares of
+    /// the provider's unbonding stake so slashes reach it pro rata.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct UndelegationEntry {
+        pub shares: u128,
+        pub unlock_block: u32,
+    }
+
+    /// Fixed-point scale of `DelegationPool::reward_per_share`.
+    const REWARD_PRECISION: u128 = 1_000_000_000_000;
+    /// Blocks before a commission increase takes effect (~1 day at 6s blocks),
+    /// giving delegators time to undelegate first.
+    pub const COMMISSION_INCREASE_DELAY: u32 = 14_400;
+
+    /// Outcome of a job as reported by an authorized reporter contract.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum JobOutcome {
+        Completed,
+        Failed,
+        Disputed,
+        /// Completed after the deadline.
+        Late,
+    }
+
+    /// Parameters of the reputation score. Each reported outcome moves the
+    /// score towards that outcome's target by `smoothing_bps` of the distance,
+    /// so older outcomes decay geometrically. Every full `value_step` of job
+    /// value adds another `smoothing_bps`, up to 100%; a `value_step` of 0
+    /// weighs all jobs equally.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct ReputationWeights {
+        pub completed_target: u32,
+        pub failed_target: u32,
+        pub disputed_target: u32,
+        pub late_target: u32,
+        pub smoothing_bps: u16,
+        pub value_step: u128,
+    }
+
+    impl Default for ReputationWeights {
+        fn default() -> Self {
+            Self {
+                completed_target: MAX_REPUTATION,
+                failed_target: 0,
+                disputed_target: 0,
+                late_target: MAX_REPUTATION / 2,
+                smoothing_bps: 500,
+                value_step: 1_000_000_000_000,
+            }
+        }
+    }
+
+    /// Upper bound of `reputation_score`.
+    pub const MAX_REPUTATION: u32 = 1_000;
+    /// Basis point denominator for reputation smoothing.
+    const BPS_DENOMINATOR: u128 = 10_000;
+
+    /// Broad class of compute hardware a provider offers.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Copy,
+        Debug,
+        Default,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum HardwareClass {
+        #[default]
+        Cpu,
+        Gpu,
+        Tpu,
+        Fpga,
+    }
+
+    /// Trusted execution environment technology.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum TeeType {
+        IntelSgx,
+        IntelTdx,
+        AmdSevSnp,
+        /// Phala Network workers.
+        Phala,
+    }
+
+    /// Structured description of what a provider can run, e.g. 4x A100 with
+    /// 80 GB memory, Phala TEE, region "eu-west".
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        Default,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Capabilities {
+        pub hardware_class: HardwareClass,
+        /// Accelerator model, e.g. "A100"; empty for CPU-only providers.
+        pub accelerator_type: String,
+        pub accelerator_count: u32,
+        /// Accelerator memory (or system memory for CPU providers) in GB.
+        pub memory_gb: u32,
+        pub tee_supported: bool,
+        /// Must be `None` unless `tee_supported` is set.
+        pub tee_type: Option<TeeType>,
+        /// Supported frameworks, e.g. "pytorch", "onnx".
+        pub frameworks: Vec<String>,
+        pub region: String,
+    }
+
+    /// Maximum number of frameworks in a capability descriptor.
+    const MAX_FRAMEWORKS: usize = 16;
+    /// Maximum byte length of each string in a capability descriptor.
+    const MAX_DESCRIPTOR_LEN: usize = 64;
+    /// Maximum number of profiles returned by `list_providers` and `find_providers`.
+    const MAX_PAGE_SIZE: u32 = 50;
+
+    /// Stake leaving the registry. It stays slashable until `unlock_block`.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct UnbondingEntry {
+        pub amount: u128,
+        pub unlock_block: u32,
+    }
+
+    /// Default blocks a stake withdrawal stays slashable (~7 days at 6s blocks).
+    pub const DEFAULT_UNBONDING_PERIOD: u32 = 100_800;
+    /// Shortest unbonding period governance may set (~1 day at 6s blocks), so
+    /// slashers always get a window to act on a withdrawal.
+    pub const MIN_UNBONDING_PERIOD: u32 = 14_400;
+    /// Maximum pending unbonding entries per provider, bounding slash and withdraw cost.
+    const MAX_UNBONDING_ENTRIES: usize = 16;
+
+    /// Registry parameter change applied through the timelocked queue.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum ParamChange {
+        MinStake(u128),
+        ReputationWeights(ReputationWeights),
+        GrantRole(Role, H160),
+        RevokeRole(Role, H160),
+        /// Applies to unbonding requests made after the change.
+        UnbondingPeriod(u32),
+        /// Return bonded stake to the provider or the treasury, skipping unbonding.
+        ForceWithdraw { provider: H160, amount: u128, to: H160 },
+        /// Receiver of slashes made without a beneficiary.
+        Treasury(H160),
+        /// Override a provider's reputation score, at most `MAX_REPUTATION`.
+        Reputation { provider: H160, score: u32 },
+    }
+
+    /// A queued parameter change.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct PendingChange {
+        pub change: ParamChange,
+        pub executable_at: u32,
+    }
+
+    /// Blocks between proposing and executing a parameter change (~1 day at 6s blocks).
+    pub const GOVERNANCE_DELAY: u32 = 14_400;
+
+    /// Permissions the admin can grant to other accounts or contracts.
+    #[derive(
+        ink::scale::Encode,
+        ink::scale::Decode,
+        Clone,
+        Copy,
+        Debug,
+        PartialEq,
+        Eq,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Role {
+        /// May call `slash`, e.g. an escrow or job queue contract.
+        Slasher,
+        /// May call `record_outcome`, e.g. the job queue, escrow or a TEE processor.
+        Reporter,
+        /// May reserve and release provider capacity, e.g. the job queue.
+        JobManager,
+        /// May issue and check endpoint challenges.
+        Verifier,
+        /// May submit liveness attestations.
+        Monitor,
     }
 
     #[ink(storage)]
@@ -39,6 +497,44 @@ mod compute_provider_registry {
         admin: H160,
         /// provider count for enumeration or stats
         provider_count: u64,
+        /// index -> provider address, dense over 0..provider_count
+        provider_at: Mapping<u64, H160>,
+        /// provider address -> index in `provider_at`
+        provider_index: Mapping<H160, u64>,
+        /// (role, account) -> granted
+        roles: Mapping<(Role, H160), ()>,
+        /// receives slashed stake when no beneficiary is given
+        treasury: H160,
+        /// parameters applied by `record_outcome`
+        reputation_weights: ReputationWeights,
+        /// (provider, delegator) -> delegation
+        delegations: Mapping<(H160, H160), Delegation>,
+        /// provider -> delegation pool
+        delegation_pools: Mapping<H160, DelegationPool>,
+        /// provider -> unclaimed commission
+        commission_rewards: Mapping<H160, u128>,
+        /// provider -> scheduled commission increase and the block it takes effect
+        pending_commissions: Mapping<H160, (u16, u32)>,
+        /// (provider, delegator) -> undelegated stake waiting out the unbonding period
+        undelegating: Mapping<(H160, H160), Vec<UndelegationEntry>>,
+        /// (provider, job_id) -> compute units reserved for the job
+        reservations: Mapping<(H160, u128), u64>,
+        /// provider -> outstanding challenge and the last block it can be answered
+        challenges: Mapping<H160, ([u8; 32], u32)>,
+        /// (monitor, provider) -> block of the monitor's last liveness probe
+        last_probe: Mapping<(H160, H160), u32>,
+        /// (monitor, provider) -> unanswered probe nonce and the block it was issued
+        probes: Mapping<(H160, H160), ([u8; 32], u32)>,
+        /// number of challenges and probes issued, mixed into each nonce
+        challenge_nonce: u64,
+        /// proposal id -> parameter change waiting out `GOVERNANCE_DELAY`
+        pending_changes: Mapping<u32, PendingChange>,
+        /// id of the next proposal
+        next_change_id: u32,
+        /// provider -> stake waiting out the unbonding period
+        unbonding: Mapping<H160, Vec<UnbondingEntry>>,
+        /// blocks between requesting an unstake and withdrawing it
+        unbonding_period: u32,
     }
 
     impl ComputeProviderRegistry {
@@ -51,12 +547,55 @@ mod compute_provider_registry {
                 min_stake,
                 admin: caller_h160,
                 provider_count: 0,
+                provider_at: Mapping::default(),
+                provider_index: Mapping::default(),
+                roles: Mapping::default(),
+                treasury: caller_h160,
+                reputation_weights: ReputationWeights::default(),
+                delegations: Mapping::default(),
+                delegation_pools: Mapping::default(),
+                commission_rewards: Mapping::default(),
+                pending_commissions: Mapping::default(),
+                undelegating: Mapping::default(),
+                reservations: Mapping::default(),
+                challenges: Mapping::default(),
+                last_probe: Mapping::default(),
+                probes: Mapping::default(),
+                challenge_nonce: 0,
+                pending_changes: Mapping::default(),
+                next_change_id: 0,
+                unbonding: Mapping::default(),
+                unbonding_period: DEFAULT_UNBONDING_PERIOD,
             }
         }
 
         /// Register as a compute provider. Requires attached stake >= min_stake.
         #[ink(message, payable)]
         pub fn register_provider(&mut self, endpoint: String, compute_units: u64, hourly_rate: u128) -> bool {
+            self.register(endpoint, compute_units, hourly_rate, Capabilities::default())
+        }
+
+        /// Register as a compute provider with a capability descriptor.
+        #[ink(message, payable)]
+        pub fn register_provider_with_capabilities(&mut self, endpoint: String, compute_units: u64, hourly_rate: u128, capabilities: Capabilities) -> bool {
+            if !Self::capabilities_valid(&capabilities) { return false; }
+            self.register(endpoint, compute_units, hourly_rate, capabilities)
+        }
+
+        /// Replace the caller's capability descriptor.
+        #[ink(message)]
+        pub fn update_capabilities(&mut self, capabilities: Capabilities) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if !Self::capabilities_valid(&capabilities) { return false; }
+            if let Some(mut profile) = self.providers.get(caller) {
+                profile.capabilities = capabilities.clone();
+                self.providers.insert(caller, &profile);
+                self.env().emit_event(CapabilitiesUpdated { provider: caller, capabilities });
+                true
+            } else { false }
+        }
+
+        fn register(&mut self, endpoint: String, compute_units: u64, hourly_rate: u128, capabilities: Capabilities) -> bool {
             let caller: H160 = self.env().caller().into();
             let stake_u256 = self.env().transferred_value();
             let stake = stake_u256.as_u128();
@@ -72,556 +611,3173 @@ mod compute_provider_registry {
                 is_active: true,
                 stake,
                 reputation_score: 100,
+                capabilities,
+                jobs_completed: 0,
+                jobs_failed: 0,
+                jobs_disputed: 0,
+                jobs_late: 0,
+                delegated_stake: 0,
+                commission_bps: 0,
+                active_jobs: 0,
+                reserved_units: 0,
+                suspended: false,
+                deregister_at: None,
+                pricing: PricingModel::Hourly { rate: hourly_rate, min_hours: 0 },
+                identity_key: None,
+                endpoint_verified: false,
+                sla: None,
+                uptime_bps: 0,
+                liveness_checks: 0,
             };
             self.providers.insert(caller, &profile);
+            self.provider_at.insert(self.provider_count, &caller);
+            self.provider_index.insert(caller, &self.provider_count);
             self.provider_count = self.provider_count.saturating_add(1);
             self.env().emit_event(ProviderRegistered { provider: caller, stake, compute_units });
+            if profile.capabilities != Capabilities::default() {
+                self.env().emit_event(CapabilitiesUpdated { provider: caller, capabilities: profile.capabilities });
+            }
             true
         }
 
-        /// Update provider's endpoint and hourly rate.
+        /// Units are named, and tier bands are bounded in number, increasing, and
+        /// unbounded only in last position.
+        fn pricing_valid(pricing: &PricingModel) -> bool {
+            match pricing {
+                PricingModel::Hourly { .. } => true,
+                PricingModel::PerUnit { unit, .. } => !unit.is_empty() && unit.len() <= MAX_DESCRIPTOR_LEN,
+                PricingModel::Tiered(tiers) => {
+                    if tiers.is_empty() || tiers.len() > MAX_PRICE_TIERS { return false; }
+                    let mut previous = 0u64;
+                    for (i, tier) in tiers.iter().enumerate() {
+                        match tier.up_to {
+                            Some(up_to) if up_to > previous => previous = up_to,
+                            None if i == tiers.len() - 1 => {}
+                            _ => return false,
+                        }
+                    }
+                    true
+                }
+            }
+        }
+
+        /// A TEE type needs TEE support, and list and string sizes are bounded.
+        fn capabilities_valid(capabilities: &Capabilities) -> bool {
+            if capabilities.tee_type.is_some() && !capabilities.tee_supported { return false; }
+            if capabilities.frameworks.len() > MAX_FRAMEWORKS { return false; }
+            capabilities.accelerator_type.len() <= MAX_DESCRIPTOR_LEN
+                && capabilities.region.len() <= MAX_DESCRIPTOR_LEN
+                && capabilities.frameworks.iter().all(|f| f.len() <= MAX_DESCRIPTOR_LEN)
+        }
+
+        /// Update provider's endpoint and hourly rate, and optionally replace the
+        /// pricing model as `update_pricing` does. An `Hourly` pricing model
+        /// follows the new rate unless `pricing` is given, in which case a new
+        /// `Hourly` model's rate wins; a new endpoint must be verified again.
         #[ink(message)]
-        pub fn update_provider(&mut self, endpoint: String, hourly_rate: Balance) -> bool {
+        pub fn update_provider(&mut self, endpoint: String, hourly_rate: Balance, pricing: Option<PricingModel>) -> bool {
             let caller: H160 = self.env().caller().into();
+            if pricing.as_ref().is_some_and(|pricing| !Self::pricing_valid(pricing)) { return false; }
             if let Some(mut profile) = self.providers.get(caller) {
+                if profile.endpoint != endpoint {
+                    profile.endpoint_verified = false;
+                    self.challenges.remove(caller);
+                }
                 profile.endpoint = endpoint.clone();
                 profile.hourly_rate = hourly_rate;
+                if let PricingModel::Hourly { rate, .. } = &mut profile.pricing { *rate = hourly_rate; }
+                if let Some(pricing) = pricing.clone() {
+                    if let PricingModel::Hourly { rate, .. } = pricing { profile.hourly_rate = rate; }
+                    profile.pricing = pricing;
+                }
                 self.providers.insert(caller, &profile);
-                self.env().emit_event(ProviderUpdated { provider: caller, endpoint, hourly_rate });
+                self.env().emit_event(ProviderUpdated { provider: caller, endpoint, hourly_rate: profile.hourly_rate });
+                if let Some(pricing) = pricing {
+                    self.env().emit_event(PricingUpdated { provider: caller, pricing });
+                }
                 true
             } else { false }
         }
 
-        /// Set provider as active or inactive.
+        /// Bind the key the caller's endpoint will sign challenges with. Clears
+        /// any previous verification and outstanding challenge.
         #[ink(message)]
-        pub fn set_active(&mut self, is_active: bool) -> bool {
+        pub fn bind_identity_key(&mut self, key: IdentityKey) -> bool {
             let caller: H160 = self.env().caller().into();
             if let Some(mut profile) = self.providers.get(caller) {
-                profile.is_active = is_active;
+                profile.identity_key = Some(key);
+                profile.endpoint_verified = false;
                 self.providers.insert(caller, &profile);
-                self.env().emit_event(ProviderActiveChanged { provider: caller, is_active });
+                self.challenges.remove(caller);
+                self.env().emit_event(IdentityKeyBound { provider: caller, key });
                 true
             } else { false }
         }
 
-        /// Increase provider's stake (payable).
-        #[ink(message, payable)]
-        pub fn add_stake(&mut self) -> bool {
+        /// Issue a fresh challenge for a provider with a bound key, replacing any
+        /// outstanding one. Only callable by the admin or a `Verifier`. The
+        /// provider's endpoint is expected to sign the returned bytes with its
+        /// identity key.
+        #[ink(message)]
+        pub fn issue_challenge(&mut self, provider: H160) -> Option<[u8; 32]> {
             let caller: H160 = self.env().caller().into();
-            let amount_u256 = self.env().transferred_value();
-            let amount = amount_u256.as_u128();
-            if amount == 0 { return false; }
+            if caller != self.admin && !self.has_role(Role::Verifier, caller) { return None; }
+            let profile = self.providers.get(provider)?;
+            let key = profile.identity_key?;
+
+            self.challenge_nonce = self.challenge_nonce.saturating_add(1);
+            let encoded = ink::scale::Encode::encode(&(
+                CHALLENGE_DOMAIN,
+                self.env().address(),
+                provider,
+                key,
+                profile.endpoint,
+                self.challenge_nonce,
+                self.env().block_number(),
+            ));
+            let challenge = self.env().hash_bytes::<Keccak256>(&encoded);
+            let expires_at = self.env().block_number().saturating_add(CHALLENGE_VALIDITY);
+            self.challenges.insert(provider, &(challenge, expires_at));
+            self.env().emit_event(ChallengeIssued { provider, challenge, expires_at });
+            Some(challenge)
+        }
+
+        /// Check the provider's signature over its outstanding challenge and mark
+        /// the endpoint verified. Only callable by the admin or a `Verifier`.
+        /// Sr25519 keys take a 64-byte signature; ECDSA keys a 65-byte
+        /// recoverable signature over the challenge used as the message hash.
+        #[ink(message)]
+        pub fn verify_endpoint_challenge(&mut self, provider: H160, signature: Vec<u8>) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if caller != self.admin && !self.has_role(Role::Verifier, caller) { return false; }
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+            let Some(key) = profile.identity_key else { return false; };
+            let Some((challenge, expires_at)) = self.challenges.get(provider) else { return false; };
+            if self.env().block_number() > expires_at { return false; }
+
+            if !self.signed_by(key, &challenge, &signature) { return false; }
+
+            self.challenges.remove(provider);
+            profile.endpoint_verified = true;
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(EndpointVerified { provider, verifier: caller });
+            true
+        }
+
+        /// Outstanding challenge for a provider, if any.
+        #[ink(message)]
+        pub fn get_challenge(&self, provider: H160) -> Option<[u8; 32]> { self.challenges.get(provider).map(|(challenge, _)| challenge) }
+
+        /// Whether `signature` is `key`'s signature over `message`: 64 bytes for
+        /// Sr25519 keys, or a 65-byte recoverable ECDSA signature with `message`
+        /// used as the hash.
+        fn signed_by(&self, key: IdentityKey, message: &[u8; 32], signature: &[u8]) -> bool {
+            match key {
+                IdentityKey::Sr25519(public) => {
+                    let Ok(signature) = <[u8; 64]>::try_from(signature) else { return false; };
+                    self.env().sr25519_verify(&signature, message, &public).is_ok()
+                }
+                IdentityKey::Ecdsa(public) => {
+                    let Ok(signature) = <[u8; 65]>::try_from(signature) else { return false; };
+                    let mut recovered = [0u8; 33];
+                    self.env().ecdsa_recover(&signature, message, &mut recovered).is_ok() && recovered == public
+                }
+            }
+        }
+
+        /// Replace the caller's pricing model. Switching to `Hourly` also
+        /// updates `hourly_rate`.
+        #[ink(message)]
+        pub fn update_pricing(&mut self, pricing: PricingModel) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if !Self::pricing_valid(&pricing) { return false; }
             if let Some(mut profile) = self.providers.get(caller) {
-                profile.stake = profile.stake.saturating_add(amount);
+                if let PricingModel::Hourly { rate, .. } = pricing { profile.hourly_rate = rate; }
+                profile.pricing = pricing.clone();
                 self.providers.insert(caller, &profile);
-                self.env().emit_event(StakeAdded { provider: caller, amount });
+                self.env().emit_event(PricingUpdated { provider: caller, pricing });
                 true
             } else { false }
         }
 
-        /// Withdraw stake (only if provider inactive or by admin).
+        /// Price of running `units` for `duration_secs` with the provider's
+        /// pricing model. `None` if the provider is unknown, the units fall
+        /// outside every tier, or the price overflows.
         #[ink(message)]
-        pub fn withdraw_stake(&mut self, amount: u128) -> bool {
+        pub fn quote(&self, provider: H160, units: u64, duration_secs: u64) -> Option<u128> {
+            let profile = self.providers.get(provider)?;
+            match profile.pricing {
+                PricingModel::Hourly { rate, min_hours } => {
+                    let hours = duration_secs.div_ceil(SECONDS_PER_HOUR).max(u64::from(min_hours));
+                    rate.checked_mul(u128::from(units))?.checked_mul(u128::from(hours))
+                }
+                PricingModel::PerUnit { price, .. } => price.checked_mul(u128::from(units)),
+                PricingModel::Tiered(tiers) => {
+                    let mut total = 0u128;
+                    let mut priced = 0u64;
+                    for tier in tiers {
+                        if priced >= units { break; }
+                        let band_end = tier.up_to.unwrap_or(u64::MAX).min(units);
+                        let band = band_end.saturating_sub(priced);
+                        total = total.checked_add(tier.price_per_unit.checked_mul(u128::from(band))?)?;
+                        priced = band_end;
+                    }
+                    if priced < units { None } else { Some(total) }
+                }
+            }
+        }
+
+        /// Set provider as active or inactive. Activating requires own plus
+        /// delegated stake >= min_stake, and is not possible while suspended
+        /// or deregistering.
+        #[ink(message)]
+        pub fn set_active(&mut self, is_active: bool) -> bool {
             let caller: H160 = self.env().caller().into();
             if let Some(mut profile) = self.providers.get(caller) {
-                if profile.is_active && caller != self.admin { return false; }
-                if profile.stake < amount { return false; }
-                let amount_u256 = U256::from(amount);
-                if self.env().transfer(caller, amount_u256).is_err() { return false; }
-                profile.stake = profile.stake.saturating_sub(amount);
+                if is_active && (profile.suspended || profile.deregister_at.is_some()) { return false; }
+                if is_active && Self::bonded(&profile) < self.min_stake { return false; }
+                profile.is_active = is_active;
                 self.providers.insert(caller, &profile);
-                self.env().emit_event(StakeWithdrawn { provider: caller, amount });
+                self.env().emit_event(ProviderActiveChanged { provider: caller, is_active });
                 true
             } else { false }
         }
 
-        /// Admin adjusts reputation score.
+        /// Increase provider's stake (payable). Reverts, returning the value,
+        /// for unregistered or deregistering callers.
+        #[ink(message, payable)]
+        pub fn add_stake(&mut self) -> bool {
+            let caller: H160 = self.env().caller().into();
+            let amount_u256 = self.env().transferred_value();
+            let amount = amount_u256.as_u128();
+            assert!(amount > 0, "Nothing staked");
+            let Some(mut profile) = self.providers.get(caller) else { panic!("Not a provider") };
+            assert!(profile.deregister_at.is_none(), "Provider is deregistering");
+            profile.stake = profile.stake.saturating_add(amount);
+            self.providers.insert(caller, &profile);
+            self.env().emit_event(StakeAdded { provider: caller, amount });
+            true
+        }
+
+        /// Withdraw stake (only if provider inactive). The amount enters the
+        /// unbonding queue and is paid out by `withdraw_unbonded`.
         #[ink(message)]
-        pub fn set_reputation(&mut self, provider: H160, score: u32) -> bool {
+        pub fn withdraw_stake(&mut self, amount: u128) -> bool {
             let caller: H160 = self.env().caller().into();
-            if caller != self.admin { return false; }
-            if let Some(mut profile) = self.providers.get(provider) {
-                profile.reputation_score = score;
-                self.providers.insert(provider, &profile);
-                self.env().emit_event(ReputationUpdated { provider, score });
-                true
+            if let Some(profile) = self.providers.get(caller) {
+                if profile.is_active { return false; }
+                self.queue_unbonding(caller, profile, amount)
             } else { false }
         }
 
-        /// Get provider profile.
+        /// Start unbonding `amount` of stake. An active provider must keep at
+        /// least `min_stake` including delegations; the amount stays slashable
+        /// until it unlocks.
         #[ink(message)]
-        pub fn get_provider(&self, provider: H160) -> Option<ProviderProfile> { self.providers.get(provider) }
+        pub fn request_unstake(&mut self, amount: u128) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if let Some(profile) = self.providers.get(caller) {
+                if profile.is_active && Self::bonded(&profile).saturating_sub(amount) < self.min_stake { return false; }
+                self.queue_unbonding(caller, profile, amount)
+            } else { false }
+        }
 
-        /// Get admin address.
+        /// Pay out every unbonding entry of the caller's own stake whose unlock
+        /// block has been reached. Not possible while suspended. Undelegated
+        /// stake is paid by `withdraw_undelegated`.
         #[ink(message)]
-        pub fn get_admin(&self) -> H160 { self.admin }
+        pub fn withdraw_unbonded(&mut self) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if self.providers.get(caller).is_some_and(|profile| profile.suspended) { return false; }
+            let entries = self.unbonding.get(caller).unwrap_or_default();
+            let (amount, pending) = Self::split_matured(&entries, self.env().block_number());
+            if amount == 0 { return false; }
 
-        /// Get provider count.
+            Self::store_entries(&mut self.unbonding, caller, pending);
+            if self.env().transfer(caller, U256::from(amount)).is_err() {
+                Self::store_entries(&mut self.unbonding, caller, entries);
+                return false;
+            }
+            self.env().emit_event(StakeWithdrawn { provider: caller, amount });
+            true
+        }
+
+        /// Get a provider's pending unbonding entries.
         #[ink(message)]
-        pub fn get_provider_count(&self) -> u64 { self.provider_count }
+        pub fn get_unbonding(&self, provider: H160) -> Vec<UnbondingEntry> { self.unbonding.get(provider).unwrap_or_default() }
 
-        /// Get min stake requirement.
+        /// Get the unbonding period in blocks.
         #[ink(message)]
-        pub fn get_min_stake(&self) -> u128 { self.min_stake }
+        pub fn get_unbonding_period(&self) -> u32 { self.unbonding_period }
 
-        /// Admin sets min stake.
+        /// Reserve `units` of a provider's capacity for `job_id`. Only callable by
+        /// accounts with the `JobManager` role. Fails if the provider is inactive
+        /// or below `min_stake`, the job already holds a reservation, or the
+        /// provider lacks free units.
         #[ink(message)]
-        pub fn set_min_stake(&mut self, new_min_stake: u128) -> bool {
+        pub fn reserve_capacity(&mut self, provider: H160, units: u64, job_id: u128) -> bool {
             let caller: H160 = self.env().caller().into();
-            if caller != self.admin { return false; }
-            self.min_stake = new_min_stake;
+            if !self.has_role(Role::JobManager, caller) { return false; }
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+            if !self.is_eligible(&profile) || units == 0 { return false; }
+            if self.reservations.contains((provider, job_id)) { return false; }
+            if units > profile.compute_units.saturating_sub(profile.reserved_units) { return false; }
+
+            profile.reserved_units = profile.reserved_units.saturating_add(units);
+            profile.active_jobs = profile.active_jobs.saturating_add(1);
+            self.reservations.insert((provider, job_id), &units);
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(CapacityReserved { provider, job_id, units });
             true
         }
-    }
 
-    #[ink(event)]
-    pub struct ProviderRegistered { #[ink(topic)] pub provider: H160, pub stake: u128, pub compute_units: u64 }
-    #[ink(event)]
-    pub struct ProviderUpdated { #[ink(topic)] pub provider: H160, pub endpoint: String, pub hourly_rate: u128 }
-    #[ink(event)]
-    pub struct ProviderActiveChanged { #[ink(topic)] pub provider: H160, pub is_active: bool }
-    #[ink(event)]
-    pub struct StakeAdded { #[ink(topic)] pub provider: H160, pub amount: u128 }
-    #[ink(event)]
-    pub struct StakeWithdrawn { #[ink(topic)] pub provider: H160, pub amount: u128 }
-    #[ink(event)]
-    pub struct ReputationUpdated { #[ink(topic)] pub provider: H160, pub score: u32 }
+        /// Release the capacity reserved for `job_id`. Only callable by accounts
+        /// with the `JobManager` role.
+        #[ink(message)]
+        pub fn release_capacity(&mut self, provider: H160, job_id: u128) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if !self.has_role(Role::JobManager, caller) { return false; }
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+            let Some(units) = self.reservations.take((provider, job_id)) else { return false; };
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+            profile.reserved_units = profile.reserved_units.saturating_sub(units);
+            profile.active_jobs = profile.active_jobs.saturating_sub(1);
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(CapacityReleased { provider, job_id, units });
+            true
+        }
 
-        fn alice() -> H160 { H160::from([0x1; 20]) }
-        fn bob() -> H160 { H160::from([0x2; 20]) }
-        fn charlie() -> H160 { H160::from([0x3; 20]) }
+        /// Units reserved for a job, if any.
+        #[ink(message)]
+        pub fn get_reservation(&self, provider: H160, job_id: u128) -> Option<u64> { self.reservations.get((provider, job_id)) }
 
-        fn set_caller(account: H160) {
-            ink::env::test::set_caller(account.into());
+        /// Compute units not yet reserved; 0 for inactive or unknown providers
+        /// and providers below `min_stake`.
+        #[ink(message)]
+        pub fn available_units(&self, provider: H160) -> u64 {
+            match self.providers.get(provider) {
+                Some(profile) if self.is_eligible(&profile) => profile.compute_units.saturating_sub(profile.reserved_units),
+                _ => 0,
+            }
         }
 
-        fn set_value(amount: u128) {
-            ink::env::test::set_value_transferred(amount.into());
-        }
+        /// Leave the registry. Requires no active jobs, no delegated stake and no
+        /// suspension. The provider is deactivated, its whole stake starts
+        /// unbonding, and `complete_deregistration` removes the profile once the
+        /// unbonding period has passed. Stake stays slashable until then.
+        #[ink(message)]
+        pub fn deregister(&mut self) -> bool {
+            let caller: H160 = self.env().caller().into();
+            let Some(mut profile) = self.providers.get(caller) else { return false; };
+            if profile.deregister_at.is_some() || profile.suspended { return false; }
+            if profile.active_jobs > 0 || self.get_delegation_pool(caller).total_shares > 0 { return false; }
 
-        #[ink::test]
-        fn new_works() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let registry = ComputeProviderRegistry::new(min_stake);
-            
-            assert_eq!(registry.get_min_stake(), min_stake);
-            assert_eq!(registry.get_admin(), alice());
-            assert_eq!(registry.get_provider_count(), 0);
+            let deregister_at = self.env().block_number().saturating_add(self.unbonding_period);
+            let was_active = profile.is_active;
+            profile.is_active = false;
+            profile.deregister_at = Some(deregister_at);
+            let stake = profile.stake;
+            // `queue_unbonding` stores the profile along with the reduced stake
+            if stake > 0 {
+                if !self.queue_unbonding(caller, profile, stake) { return false; }
+            } else {
+                self.providers.insert(caller, &profile);
+            }
+            if was_active {
+                self.env().emit_event(ProviderActiveChanged { provider: caller, is_active: false });
+            }
+            self.env().emit_event(DeregistrationRequested { provider: caller, deregister_at });
+            true
         }
 
-        #[ink::test]
-        fn register_provider_works() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(1000);
-            
-            assert!(registry.register_provider(
-                "http://provider.com".to_string(),
-                100, // compute_units
-                50u128 // hourly_rate
-            ));
-            
-            assert_eq!(registry.get_provider_count(), 1);
-            
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.provider, bob());
-            assert_eq!(profile.endpoint, "http://provider.com");
-            assert_eq!(profile.compute_units, 100);
-            assert_eq!(profile.hourly_rate, 50u128);
-            assert_eq!(profile.is_active, true);
-            assert_eq!(profile.stake, 1000u128);
-            assert_eq!(profile.reputation_score, 100);
+        /// Remove the caller's profile once its deregistration window has passed.
+        /// Fails while delegations remain. Unbonding stake, undelegated stake and
+        /// unclaimed commission stay withdrawable.
+        #[ink(message)]
+        pub fn complete_deregistration(&mut self) -> bool {
+            let caller: H160 = self.env().caller().into();
+            let Some(profile) = self.providers.get(caller) else { return false; };
+            match profile.deregister_at {
+                Some(at) if self.env().block_number() >= at => {}
+                _ => return false,
+            }
+            if profile.suspended { return false; }
+            let pool = self.get_delegation_pool(caller);
+            if pool.total_shares > 0 { return false; }
+
+            self.providers.remove(caller);
+            // Undelegations still unbonding are paid from the pool
+            if pool.unbonding_shares == 0 { self.delegation_pools.remove(caller); }
+            self.remove_from_index(caller);
+            self.provider_count = self.provider_count.saturating_sub(1);
+            self.env().emit_event(ProviderDeregistered { provider: caller });
+            true
         }
 
-        #[ink::test]
-        fn register_provider_insufficient_stake_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(500); // Insufficient stake
-            
-            assert!(!registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            ));
-            
-            assert_eq!(registry.get_provider_count(), 0);
-            assert!(registry.get_provider(bob()).is_none());
+        /// Admin suspends a provider under investigation. It is deactivated and
+        /// cannot reactivate, unstake, withdraw unbonded stake or deregister until
+        /// unsuspended. Not timelocked, so the provider cannot exit first.
+        #[ink(message)]
+        pub fn suspend(&mut self, provider: H160) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if caller != self.admin { return false; }
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+            if profile.suspended { return false; }
+            let was_active = profile.is_active;
+            profile.suspended = true;
+            profile.is_active = false;
+            self.providers.insert(provider, &profile);
+            if was_active {
+                self.env().emit_event(ProviderActiveChanged { provider, is_active: false });
+            }
+            self.env().emit_event(ProviderSuspended { provider, suspended: true });
+            true
         }
 
-        #[ink::test]
-        fn register_provider_already_registered_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(1000);
-            
-            // First registration
-            assert!(registry.register_provider(
-                "http://provider1.com".to_string(),
-                100,
-                50u128
-            ));
-            
-            // Attempt second registration
-            set_value(1000);
-            assert!(!registry.register_provider(
-                "http://provider2.com".to_string(),
-                200,
-                75u128
-            ));
-            
-            // Original data should remain unchanged
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.endpoint, "http://provider1.com");
-            assert_eq!(profile.compute_units, 100);
+        /// Admin lifts a suspension. The provider stays inactive until it calls `set_active`.
+        #[ink(message)]
+        pub fn unsuspend(&mut self, provider: H160) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if caller != self.admin { return false; }
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+            if !profile.suspended { return false; }
+            profile.suspended = false;
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(ProviderSuspended { provider, suspended: false });
+            true
         }
 
-        #[ink::test]
-        fn update_provider_works() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(1000);
-            registry.register_provider(
-                "http://old.com".to_string(),
-                100,
-                50u128
-            );
-            
-            assert!(registry.update_provider(
-                "http://new.com".to_string(),
-                75u128
-            ));
-            
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.endpoint, "http://new.com");
-            assert_eq!(profile.hourly_rate, 75u128);
-            assert_eq!(profile.compute_units, 100); // Unchanged
+        /// Slash up to `amount` of a provider's stake and pay it to `beneficiary`,
+        /// or to the treasury if `None`. Only callable by accounts with the
+        /// `Slasher` role. Bonded stake is slashed first, split pro rata between
+        /// the provider and its delegators, then unbonding stake, split pro rata
+        /// between the provider's entries (from the most recent) and its
+        /// delegators' undelegations; an active provider left below `min_stake`
+        /// is deactivated.
+        #[ink(message)]
+        pub fn slash(&mut self, provider: H160, amount: u128, reason_code: u8, beneficiary: Option<H160>) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if !self.has_role(Role::Slasher, caller) { return false; }
+            let Some(original) = self.providers.get(provider) else { return false; };
+            let original_unbonding = self.unbonding.get(provider).unwrap_or_default();
+            let original_pool = self.delegation_pools.get(provider).unwrap_or_default();
+
+            let mut profile = original.clone();
+            let bonded = Self::bonded(&profile);
+            let from_bonded = amount.min(bonded);
+            let from_delegated = Self::mul_div(from_bonded, profile.delegated_stake, bonded);
+            profile.stake = profile.stake.saturating_sub(from_bonded.saturating_sub(from_delegated));
+            profile.delegated_stake = profile.delegated_stake.saturating_sub(from_delegated);
+
+            // The rest comes from own and undelegated unbonding stake pro rata
+            let mut remaining = amount.saturating_sub(from_bonded);
+            let mut pool = original_pool.clone();
+            let own_unbonding = original_unbonding.iter().fold(0u128, |sum, e| sum.saturating_add(e.amount));
+            let unbonding_total = own_unbonding.saturating_add(pool.unbonding);
+            let from_undelegated = Self::mul_div(remaining.min(unbonding_total), pool.unbonding, unbonding_total);
+            pool.unbonding = pool.unbonding.saturating_sub(from_undelegated);
+            remaining = remaining.saturating_sub(from_undelegated);
+            let from_delegators = from_delegated.saturating_add(from_undelegated);
+            let mut unbonding = original_unbonding.clone();
+            for entry in unbonding.iter_mut().rev() {
+                if remaining == 0 { break; }
+                let taken = remaining.min(entry.amount);
+                entry.amount = entry.amount.saturating_sub(taken);
+                remaining = remaining.saturating_sub(taken);
+            }
+            unbonding.retain(|e| e.amount > 0);
+            let slashed = amount.saturating_sub(remaining);
+            if slashed == 0 { return false; }
+
+            let deactivated = profile.is_active && Self::bonded(&profile) < self.min_stake;
+            if deactivated { profile.is_active = false; }
+            self.providers.insert(provider, &profile);
+            Self::store_entries(&mut self.unbonding, provider, unbonding);
+            if from_undelegated > 0 { self.delegation_pools.insert(provider, &pool); }
+
+            let recipient = beneficiary.unwrap_or(self.treasury);
+            if self.env().transfer(recipient, U256::from(slashed)).is_err() {
+                self.providers.insert(provider, &original);
+                Self::store_entries(&mut self.unbonding, provider, original_unbonding);
+                if from_undelegated > 0 { self.delegation_pools.insert(provider, &original_pool); }
+                return false;
+            }
+
+            if deactivated {
+                self.env().emit_event(ProviderActiveChanged { provider, is_active: false });
+            }
+            self.env().emit_event(Slashed { provider, slasher: caller, amount: slashed, from_delegators, reason_code, beneficiary: recipient });
+            true
         }
 
-        #[ink::test]
-        fn update_provider_not_registered_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            assert!(!registry.update_provider(
-                "http://new.com".to_string(),
-                75u128
-            ));
+        /// Delegate the transferred value to `provider`. Delegated stake counts
+        /// toward the provider's `min_stake` and is slashed pro rata with it.
+        /// Reverts, returning the value, if the delegation is rejected.
+        #[ink(message, payable)]
+        pub fn delegate(&mut self, provider: H160) -> bool {
+            let caller: H160 = self.env().caller().into();
+            let amount = self.env().transferred_value().as_u128();
+            assert!(amount > 0, "Nothing delegated");
+            let Some(mut profile) = self.providers.get(provider) else { panic!("Unknown provider") };
+            assert!(profile.deregister_at.is_none(), "Provider is deregistering");
+            let mut pool = self.delegation_pools.get(provider).unwrap_or_default();
+            let shares = if pool.total_shares == 0 {
+                amount
+            } else {
+                // A pool slashed to zero has worthless shares and takes no new stake
+                assert!(profile.delegated_stake > 0, "Delegation pool slashed to zero");
+                Self::mul_div(amount, pool.total_shares, profile.delegated_stake)
+            };
+            assert!(shares > 0, "Delegation too small");
+
+            let mut delegation = self.delegations.get((provider, caller)).unwrap_or_default();
+            Self::settle_rewards(&pool, &mut delegation);
+            delegation.shares = delegation.shares.saturating_add(shares);
+            delegation.reward_debt = Self::mul_div(delegation.shares, pool.reward_per_share, REWARD_PRECISION);
+            pool.total_shares = pool.total_shares.saturating_add(shares);
+            profile.delegated_stake = profile.delegated_stake.saturating_add(amount);
+
+            self.delegations.insert((provider, caller), &delegation);
+            self.delegation_pools.insert(provider, &pool);
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(Delegated { provider, delegator: caller, amount, shares });
+            true
         }
 
-        #[ink::test]
-        fn set_active_works() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(1000);
+        /// Withdraw `amount` of delegated stake from `provider`. The amount stays
+        /// slashable while it unbonds and is paid out by `withdraw_undelegated`.
+        /// An active provider left below `min_stake` is deactivated.
+        #[ink(message)]
+        pub fn undelegate(&mut self, provider: H160, amount: u128) -> bool {
+            let caller: H160 = self.env().caller().into();
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+            let Some(mut pool) = self.delegation_pools.get(provider) else { return false; };
+            let Some(mut delegation) = self.delegations.get((provider, caller)) else { return false; };
+            let value = Self::mul_div(delegation.shares, profile.delegated_stake, pool.total_shares);
+            if amount == 0 || amount > value { return false; }
+            let mut entries = self.undelegating.get((provider, caller)).unwrap_or_default();
+            if entries.len() >= MAX_UNBONDING_ENTRIES { return false; }
+
+            // Round the burn up so remaining shares never gain value
+            let burned = if amount == value {
+                delegation.shares
+            } else {
+                Self::mul_div_ceil(amount, pool.total_shares, profile.delegated_stake).min(delegation.shares)
+            };
+            Self::settle_rewards(&pool, &mut delegation);
+            delegation.shares = delegation.shares.saturating_sub(burned);
+            delegation.reward_debt = Self::mul_div(delegation.shares, pool.reward_per_share, REWARD_PRECISION);
+            pool.total_shares = pool.total_shares.saturating_sub(burned);
+            profile.delegated_stake = profile.delegated_stake.saturating_sub(amount);
+            let deactivated = profile.is_active && Self::bonded(&profile) < self.min_stake;
+            if deactivated { profile.is_active = false; }
+
+            let unbonding_shares = if pool.unbonding_shares == 0 {
+                amount
+            } else {
+                // Unbonding slashed to zero has worthless shares and takes no new stake
+                if pool.unbonding == 0 { return false; }
+                Self::mul_div(amount, pool.unbonding_shares, pool.unbonding)
+            };
+            pool.unbonding = pool.unbonding.saturating_add(amount);
+            pool.unbonding_shares = pool.unbonding_shares.saturating_add(unbonding_shares);
+            let unlock_block = self.env().block_number().saturating_add(self.unbonding_period);
+            entries.push(UndelegationEntry { shares: unbonding_shares, unlock_block });
+            self.undelegating.insert((provider, caller), &entries);
+            self.store_delegation(provider, caller, delegation);
+            self.delegation_pools.insert(provider, &pool);
+            self.providers.insert(provider, &profile);
+
+            if deactivated {
+                self.env().emit_event(ProviderActiveChanged { provider, is_active: false });
+            }
+            self.env().emit_event(Undelegated { provider, delegator: caller, amount, unlock_block });
+            true
+        }
+
+        /// Pay out the caller's undelegations from `provider` whose unlock block
+        /// has been reached, less any slashes taken while they unbonded.
+        #[ink(message)]
+        pub fn withdraw_undelegated(&mut self, provider: H160) -> bool {
+            let caller: H160 = self.env().caller().into();
+            let now = self.env().block_number();
+            let entries = self.undelegating.get((provider, caller)).unwrap_or_default();
+            let (matured, pending): (Vec<_>, Vec<_>) = entries.iter().cloned().partition(|e| e.unlock_block <= now);
+            if matured.is_empty() { return false; }
+
+            let original_pool = self.delegation_pools.get(provider).unwrap_or_default();
+            let mut pool = original_pool.clone();
+            let mut amount = 0u128;
+            for entry in &matured {
+                let value = Self::mul_div(entry.shares, pool.unbonding, pool.unbonding_shares);
+                pool.unbonding = pool.unbonding.saturating_sub(value);
+                pool.unbonding_shares = pool.unbonding_shares.saturating_sub(entry.shares);
+                amount = amount.saturating_add(value);
+            }
+
+            if pending.is_empty() {
+                self.undelegating.remove((provider, caller));
+            } else {
+                self.undelegating.insert((provider, caller), &pending);
+            }
+            self.delegation_pools.insert(provider, &pool);
+            if amount > 0 && self.env().transfer(caller, U256::from(amount)).is_err() {
+                self.undelegating.insert((provider, caller), &entries);
+                self.delegation_pools.insert(provider, &original_pool);
+                return false;
+            }
+            self.env().emit_event(UndelegationWithdrawn { provider, delegator: caller, amount });
+            true
+        }
+
+        /// Provider sets the commission kept from distributed rewards. Decreases
+        /// apply immediately; increases take effect after `COMMISSION_INCREASE_DELAY`
+        /// blocks. A new call replaces any scheduled increase.
+        #[ink(message)]
+        pub fn set_commission(&mut self, commission_bps: u16) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if u128::from(commission_bps) > BPS_DENOMINATOR { return false; }
+            let Some(mut profile) = self.providers.get(caller) else { return false; };
+            self.pending_commissions.remove(caller);
+            let now = self.env().block_number();
+            let effective_at = if commission_bps > profile.commission_bps {
+                let effective_at = now.saturating_add(COMMISSION_INCREASE_DELAY);
+                self.pending_commissions.insert(caller, &(commission_bps, effective_at));
+                effective_at
+            } else {
+                profile.commission_bps = commission_bps;
+                self.providers.insert(caller, &profile);
+                now
+            };
+            self.env().emit_event(CommissionSet { provider: caller, commission_bps, effective_at });
+            true
+        }
+
+        /// Get a provider's scheduled commission increase and the block it takes effect.
+        #[ink(message)]
+        pub fn get_pending_commission(&self, provider: H160) -> Option<(u16, u32)> { self.pending_commissions.get(provider) }
+
+        /// Share the transferred value, e.g. job revenue, between `provider`
+        /// (its commission) and its delegators in proportion to their shares.
+        /// Without delegators everything goes to the provider. Reverts,
+        /// returning the value, for an unknown provider.
+        #[ink(message, payable)]
+        pub fn distribute_rewards(&mut self, provider: H160) -> bool {
+            let amount = self.env().transferred_value().as_u128();
+            assert!(amount > 0, "Nothing distributed");
+            let Some(mut profile) = self.providers.get(provider) else { panic!("Unknown provider") };
+            self.apply_pending_commission(provider, &mut profile);
+            let mut pool = self.delegation_pools.get(provider).unwrap_or_default();
+
+            let commission = if pool.total_shares == 0 {
+                amount
+            } else {
+                Self::mul_div(amount, u128::from(profile.commission_bps), BPS_DENOMINATOR)
+            };
+            let shared = amount.saturating_sub(commission);
+            if shared > 0 {
+                pool.reward_per_share = pool.reward_per_share.saturating_add(Self::mul_div(shared, REWARD_PRECISION, pool.total_shares));
+                self.delegation_pools.insert(provider, &pool);
+            }
+            let earned = self.commission_rewards.get(provider).unwrap_or(0);
+            self.commission_rewards.insert(provider, &earned.saturating_add(commission));
+            self.env().emit_event(RewardsDistributed { provider, amount, commission });
+            true
+        }
+
+        /// Claim the caller's rewards from `provider`'s pool, plus the
+        /// commission if the caller is the provider.
+        #[ink(message)]
+        pub fn claim_rewards(&mut self, provider: H160) -> bool {
+            let caller: H160 = self.env().caller().into();
+            let original = self.delegations.get((provider, caller));
+            let mut amount = 0u128;
+            if let Some(mut delegation) = original.clone() {
+                let pool = self.delegation_pools.get(provider).unwrap_or_default();
+                Self::settle_rewards(&pool, &mut delegation);
+                amount = delegation.pending_rewards;
+                delegation.pending_rewards = 0;
+                self.store_delegation(provider, caller, delegation);
+            }
+            let commission = if caller == provider { self.commission_rewards.take(provider).unwrap_or(0) } else { 0 };
+            amount = amount.saturating_add(commission);
+            if amount == 0 { return false; }
+
+            if self.env().transfer(caller, U256::from(amount)).is_err() {
+                if let Some(delegation) = original { self.delegations.insert((provider, caller), &delegation); }
+                if commission > 0 { self.commission_rewards.insert(provider, &commission); }
+                return false;
+            }
+            self.env().emit_event(RewardsClaimed { provider, account: caller, amount });
+            true
+        }
+
+        /// Get a delegator's position with a provider.
+        #[ink(message)]
+        pub fn get_delegation(&self, provider: H160, delegator: H160) -> Option<Delegation> { self.delegations.get((provider, delegator)) }
+
+        /// Get a provider's delegation pool.
+        #[ink(message)]
+        pub fn get_delegation_pool(&self, provider: H160) -> DelegationPool { self.delegation_pools.get(provider).unwrap_or_default() }
+
+        /// Current stake value of a delegator's shares.
+        #[ink(message)]
+        pub fn get_delegated_value(&self, provider: H160, delegator: H160) -> u128 {
+            let (Some(profile), Some(delegation)) = (self.providers.get(provider), self.delegations.get((provider, delegator))) else { return 0; };
+            Self::mul_div(delegation.shares, profile.delegated_stake, self.get_delegation_pool(provider).total_shares)
+        }
+
+        /// Rewards `account` could claim from `provider` now.
+        #[ink(message)]
+        pub fn get_pending_rewards(&self, provider: H160, account: H160) -> u128 {
+            let mut amount = 0u128;
+            if let Some(mut delegation) = self.delegations.get((provider, account)) {
+                Self::settle_rewards(&self.get_delegation_pool(provider), &mut delegation);
+                amount = delegation.pending_rewards;
+            }
+            if account == provider { amount = amount.saturating_add(self.commission_rewards.get(provider).unwrap_or(0)); }
+            amount
+        }
+
+        /// Get a delegator's pending undelegations from `provider`, valued after slashes.
+        #[ink(message)]
+        pub fn get_undelegating(&self, provider: H160, delegator: H160) -> Vec<UnbondingEntry> {
+            let pool = self.get_delegation_pool(provider);
+            self.undelegating.get((provider, delegator)).unwrap_or_default().iter().map(|e| UnbondingEntry {
+                amount: Self::mul_div(e.shares, pool.unbonding, pool.unbonding_shares),
+                unlock_block: e.unlock_block,
+            }).collect()
+        }
+
+        /// Whether `account` holds `role`.
+        #[ink(message)]
+        pub fn has_role(&self, role: Role, account: H160) -> bool { self.roles.contains((role, account)) }
+
+        /// Get treasury address.
+        #[ink(message)]
+        pub fn get_treasury(&self) -> H160 { self.treasury }
+
+        /// Record a job outcome for `provider` and update its reputation score.
+        /// Only callable by accounts with the `Reporter` role.
+        #[ink(message)]
+        pub fn record_outcome(&mut self, provider: H160, outcome: JobOutcome, job_value: u128) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if !self.has_role(Role::Reporter, caller) { return false; }
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+
+            let w = &self.reputation_weights;
+            let target = match outcome {
+                JobOutcome::Completed => { profile.jobs_completed = profile.jobs_completed.saturating_add(1); w.completed_target }
+                JobOutcome::Failed => { profile.jobs_failed = profile.jobs_failed.saturating_add(1); w.failed_target }
+                JobOutcome::Disputed => { profile.jobs_disputed = profile.jobs_disputed.saturating_add(1); w.disputed_target }
+                JobOutcome::Late => { profile.jobs_late = profile.jobs_late.saturating_add(1); w.late_target }
+            };
+            let steps = if w.value_step == 0 { 1 } else { (job_value / w.value_step).saturating_add(1) };
+            let weight = u128::from(w.smoothing_bps).saturating_mul(steps).min(BPS_DENOMINATOR);
+            profile.reputation_score = Self::smooth(profile.reputation_score.min(MAX_REPUTATION), target, weight);
+
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(OutcomeRecorded { provider, reporter: caller, outcome, job_value, score: profile.reputation_score });
+            true
+        }
+
+        /// Declare (or with `None`, withdraw) the caller's SLA terms.
+        #[ink(message)]
+        pub fn set_sla_terms(&mut self, sla: Option<SlaTerms>) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if let Some(terms) = sla {
+                if terms.max_response_blocks == 0 || u128::from(terms.uptime_target_bps) > BPS_DENOMINATOR { return false; }
+            }
+            if let Some(mut profile) = self.providers.get(caller) {
+                profile.sla = sla;
+                self.providers.insert(caller, &profile);
+                self.env().emit_event(SlaTermsSet { provider: caller, sla });
+                true
+            } else { false }
+        }
+
+        /// Issue a liveness probe of `provider` for the caller to deliver. Only
+        /// callable by accounts with the `Monitor` role, once per provider per
+        /// block, for providers with a bound key, and not while the caller's
+        /// previous probe of the provider is unanswered. The provider answers by
+        /// signing the returned nonce with its identity key.
+        #[ink(message)]
+        pub fn issue_probe(&mut self, provider: H160) -> Option<[u8; 32]> {
+            let caller: H160 = self.env().caller().into();
+            if !self.has_role(Role::Monitor, caller) { return None; }
+            let profile = self.providers.get(provider)?;
+            let key = profile.identity_key?;
+            let now = self.env().block_number();
+            if self.probes.contains((caller, provider)) || self.last_probe.get((caller, provider)) == Some(now) { return None; }
+
+            self.challenge_nonce = self.challenge_nonce.saturating_add(1);
+            let encoded = ink::scale::Encode::encode(&(
+                PROBE_DOMAIN,
+                self.env().address(),
+                caller,
+                provider,
+                key,
+                self.challenge_nonce,
+                now,
+            ));
+            let nonce = self.env().hash_bytes::<Keccak256>(&encoded);
+            self.probes.insert((caller, provider), &(nonce, now));
+            self.last_probe.insert((caller, provider), &now);
+            self.env().emit_event(ProbeIssued { provider, monitor: caller, nonce });
+            Some(nonce)
+        }
+
+        /// Get `monitor`'s unanswered probe of `provider` and the block it was issued.
+        #[ink(message)]
+        pub fn get_probe(&self, monitor: H160, provider: H160) -> Option<([u8; 32], u32)> { self.probes.get((monitor, provider)) }
+
+        /// Close the caller's outstanding probe of `provider` with the provider's
+        /// signature over its nonce, or `None` if it did not answer. Only callable
+        /// by accounts with the `Monitor` role; a signature that is not the
+        /// provider's leaves the probe open. The response time is counted from
+        /// the block the probe was issued, and a signed answer within the SLA's
+        /// `max_response_blocks` (any signed answer without an SLA) passes.
+        /// Updates the rolling uptime only: reputation follows job outcomes.
+        #[ink(message)]
+        pub fn submit_liveness(&mut self, provider: H160, signature: Option<Vec<u8>>) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if !self.has_role(Role::Monitor, caller) { return false; }
+            let Some(mut profile) = self.providers.get(provider) else { return false; };
+            let Some((nonce, issued_at)) = self.probes.get((caller, provider)) else { return false; };
+            let responded = signature.is_some();
+            if let Some(signature) = signature {
+                let Some(key) = profile.identity_key else { return false; };
+                if !self.signed_by(key, &nonce, &signature) { return false; }
+            }
+            self.probes.remove((caller, provider));
+
+            let response_blocks = self.env().block_number().saturating_sub(issued_at);
+            let in_time = profile.sla.is_none_or(|sla| response_blocks <= sla.max_response_blocks);
+            let passed = responded && in_time;
+            let sample = if passed { BPS_DENOMINATOR as u32 } else { 0 };
+            profile.uptime_bps = if profile.liveness_checks == 0 { sample } else { Self::smooth(profile.uptime_bps, sample, UPTIME_SMOOTHING_BPS) };
+            profile.liveness_checks = profile.liveness_checks.saturating_add(1);
+
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(LivenessRecorded { provider, monitor: caller, passed, uptime_bps: profile.uptime_bps });
+            true
+        }
+
+        /// Whether the provider declared an SLA, has at least `MIN_LIVENESS_CHECKS`
+        /// recorded, and its rolling uptime meets the declared target.
+        #[ink(message)]
+        pub fn meets_sla(&self, provider: H160) -> bool {
+            let Some(profile) = self.providers.get(provider) else { return false; };
+            let Some(sla) = profile.sla else { return false; };
+            profile.liveness_checks >= MIN_LIVENESS_CHECKS && profile.uptime_bps >= u32::from(sla.uptime_target_bps)
+        }
+
+        /// Get the reputation parameters.
+        #[ink(message)]
+        pub fn get_reputation_weights(&self) -> ReputationWeights { self.reputation_weights.clone() }
+
+        /// Admin queues a parameter change, executable after `GOVERNANCE_DELAY`
+        /// blocks so providers can react first. Reputation scores and targets are
+        /// capped at `MAX_REPUTATION`, smoothing at 100%, and the unbonding period
+        /// must be at least `MIN_UNBONDING_PERIOD`. Returns the proposal id.
+        #[ink(message)]
+        pub fn propose_change(&mut self, change: ParamChange) -> Option<u32> {
+            let caller: H160 = self.env().caller().into();
+            if caller != self.admin { return None; }
+            if let ParamChange::ReputationWeights(weights) = &change {
+                if !Self::weights_valid(weights) { return None; }
+            }
+            if let ParamChange::UnbondingPeriod(blocks) = change {
+                if blocks < MIN_UNBONDING_PERIOD { return None; }
+            }
+            if let ParamChange::ForceWithdraw { provider, amount, to } = change {
+                if amount == 0 || (to != provider && to != self.treasury) { return None; }
+            }
+            if let ParamChange::Reputation { score, .. } = change {
+                if score > MAX_REPUTATION { return None; }
+            }
+
+            let id = self.next_change_id;
+            self.next_change_id = id.checked_add(1)?;
+            let executable_at = self.env().block_number().saturating_add(GOVERNANCE_DELAY);
+            self.pending_changes.insert(id, &PendingChange { change: change.clone(), executable_at });
+            self.env().emit_event(ChangeProposed { id, change, executable_at });
+            Some(id)
+        }
+
+        /// Apply a queued change once its delay has passed. Callable by anyone.
+        /// A force withdrawal that can no longer be paid, or a reputation change
+        /// for a provider that has left, stays queued.
+        #[ink(message)]
+        pub fn execute_change(&mut self, id: u32) -> bool {
+            let Some(pending) = self.pending_changes.get(id) else { return false; };
+            if self.env().block_number() < pending.executable_at { return false; }
+            self.pending_changes.remove(id);
+
+            match pending.change.clone() {
+                ParamChange::MinStake(min_stake) => self.min_stake = min_stake,
+                ParamChange::ReputationWeights(weights) => self.reputation_weights = weights,
+                ParamChange::GrantRole(role, account) => {
+                    self.roles.insert((role, account), &());
+                    self.env().emit_event(RoleGranted { role, account });
+                }
+                ParamChange::RevokeRole(role, account) => {
+                    self.roles.remove((role, account));
+                    self.env().emit_event(RoleRevoked { role, account });
+                }
+                ParamChange::UnbondingPeriod(blocks) => {
+                    self.unbonding_period = blocks;
+                    self.env().emit_event(UnbondingPeriodSet { blocks });
+                }
+                ParamChange::ForceWithdraw { provider, amount, to } => {
+                    if !self.force_withdraw(provider, amount, to) {
+                        self.pending_changes.insert(id, &pending);
+                        return false;
+                    }
+                }
+                ParamChange::Treasury(treasury) => {
+                    self.treasury = treasury;
+                    self.env().emit_event(TreasurySet { treasury });
+                }
+                ParamChange::Reputation { provider, score } => {
+                    let Some(mut profile) = self.providers.get(provider) else {
+                        self.pending_changes.insert(id, &pending);
+                        return false;
+                    };
+                    profile.reputation_score = score;
+                    self.providers.insert(provider, &profile);
+                    self.env().emit_event(ReputationUpdated { provider, score });
+                }
+            }
+            self.env().emit_event(ChangeExecuted { id, change: pending.change });
+            true
+        }
+
+        /// Admin drops a queued change.
+        #[ink(message)]
+        pub fn cancel_change(&mut self, id: u32) -> bool {
+            let caller: H160 = self.env().caller().into();
+            if caller != self.admin { return false; }
+            if !self.pending_changes.contains(id) { return false; }
+            self.pending_changes.remove(id);
+            self.env().emit_event(ChangeCancelled { id });
+            true
+        }
+
+        /// Get a queued change.
+        #[ink(message)]
+        pub fn get_pending_change(&self, id: u32) -> Option<PendingChange> { self.pending_changes.get(id) }
+
+        /// Get provider profile.
+        #[ink(message)]
+        pub fn get_provider(&self, provider: H160) -> Option<ProviderProfile> { self.providers.get(provider) }
+
+        /// List up to `limit` provider profiles starting at index `cursor`, in
+        /// registration order. Pass `cursor + returned length` to get the next page.
+        #[ink(message)]
+        pub fn list_providers(&self, cursor: u64, limit: u32) -> Vec<ProviderProfile> {
+            let end = cursor.saturating_add(u64::from(limit.min(MAX_PAGE_SIZE))).min(self.provider_count);
+            (cursor..end)
+                .filter_map(|i| self.provider_at.get(i))
+                .filter_map(|provider| self.providers.get(provider))
+                .collect()
+        }
+
+        /// Deactivate the active providers below `min_stake` among up to
+        /// `limit` providers starting at index `cursor`, in registration order.
+        /// Callable by anyone, so a raised minimum can be applied page by page.
+        /// Returns how many providers were deactivated.
+        #[ink(message)]
+        pub fn enforce_min_stake(&mut self, cursor: u64, limit: u32) -> u32 {
+            let end = cursor.saturating_add(u64::from(limit.min(MAX_PAGE_SIZE))).min(self.provider_count);
+            let mut deactivated = 0u32;
+            for index in cursor..end {
+                let Some(provider) = self.provider_at.get(index) else { continue; };
+                let Some(mut profile) = self.providers.get(provider) else { continue; };
+                if !profile.is_active || Self::bonded(&profile) >= self.min_stake { continue; }
+                profile.is_active = false;
+                self.providers.insert(provider, &profile);
+                self.env().emit_event(ProviderActiveChanged { provider, is_active: false });
+                deactivated = deactivated.saturating_add(1);
+            }
+            deactivated
+        }
+
+        /// Active providers holding `min_stake`, with at least `min_units`
+        /// compute units and `min_reputation`, charging at most `max_hourly_rate`,
+        /// optionally with TEE support. The rate limit applies to `Hourly` pricing; providers
+        /// priced otherwise only match when `max_hourly_rate` is `u128::MAX`.
+        /// Sorted by reputation (highest first), then hourly rate (lowest first,
+        /// other models last), and capped at `MAX_PAGE_SIZE` results. Scans every
+        /// provider, so it is meant to be queried off-chain.
+        #[ink(message)]
+        pub fn find_providers(&self, min_units: u64, max_hourly_rate: u128, min_reputation: u32, requires_tee: bool) -> Vec<ProviderProfile> {
+            let mut matches: Vec<ProviderProfile> = (0..self.provider_count)
+                .filter_map(|i| self.provider_at.get(i))
+                .filter_map(|provider| self.providers.get(provider))
+                .filter(|p| {
+                    self.is_eligible(p)
+                        && p.compute_units >= min_units
+                        && Self::hourly_rate(p) <= max_hourly_rate
+                        && p.reputation_score >= min_reputation
+                        && (!requires_tee || p.capabilities.tee_supported)
+                })
+                .collect();
+            matches.sort_by(|a, b| b.reputation_score.cmp(&a.reputation_score).then(Self::hourly_rate(a).cmp(&Self::hourly_rate(b))));
+            matches.truncate(MAX_PAGE_SIZE as usize);
+            matches
+        }
+
+        /// Get admin address.
+        #[ink(message)]
+        pub fn get_admin(&self) -> H160 { self.admin }
+
+        /// Get provider count.
+        #[ink(message)]
+        pub fn get_provider_count(&self) -> u64 { self.provider_count }
+
+        /// Get min stake requirement.
+        #[ink(message)]
+        pub fn get_min_stake(&self) -> u128 { self.min_stake }
+
+        /// Move `score` towards `target` by `weight_bps` of the distance.
+        fn smooth(score: u32, target: u32, weight_bps: u128) -> u32 {
+            let delta = u128::from(score.abs_diff(target)).saturating_mul(weight_bps) / BPS_DENOMINATOR;
+            let delta = u32::try_from(delta).unwrap_or(u32::MAX);
+            if target >= score { score.saturating_add(delta) } else { score.saturating_sub(delta) }
+        }
+
+        fn weights_valid(weights: &ReputationWeights) -> bool {
+            u128::from(weights.smoothing_bps) <= BPS_DENOMINATOR
+                && [weights.completed_target, weights.failed_target, weights.disputed_target, weights.late_target]
+                    .iter()
+                    .all(|t| *t <= MAX_REPUTATION)
+        }
+
+        /// Move `amount` of a provider's stake into the unbonding queue.
+        fn queue_unbonding(&mut self, provider: H160, mut profile: ProviderProfile, amount: u128) -> bool {
+            if profile.suspended || amount == 0 || profile.stake < amount { return false; }
+            let mut entries = self.unbonding.get(provider).unwrap_or_default();
+            if entries.len() >= MAX_UNBONDING_ENTRIES { return false; }
+            let unlock_block = self.env().block_number().saturating_add(self.unbonding_period);
+            entries.push(UnbondingEntry { amount, unlock_block });
+            self.unbonding.insert(provider, &entries);
+            profile.stake = profile.stake.saturating_sub(amount);
+            self.providers.insert(provider, &profile);
+            self.env().emit_event(UnstakeRequested { provider, amount, unlock_block });
+            true
+        }
+
+        /// Store an account's unbonding entries, dropping the entry once empty.
+        fn store_entries(map: &mut Mapping<H160, Vec<UnbondingEntry>>, account: H160, entries: Vec<UnbondingEntry>) {
+            if entries.is_empty() { map.remove(account); } else { map.insert(account, &entries); }
+        }
+
+        /// Sum of matured entries, and the entries still locked at `now`.
+        fn split_matured(entries: &[UnbondingEntry], now: u32) -> (u128, Vec<UnbondingEntry>) {
+            let matured = entries.iter().filter(|e| e.unlock_block <= now).fold(0u128, |sum, e| sum.saturating_add(e.amount));
+            (matured, entries.iter().filter(|e| e.unlock_block > now).cloned().collect())
+        }
+
+        /// Swap-remove `provider` from the dense enumeration index.
+        fn remove_from_index(&mut self, provider: H160) {
+            let Some(index) = self.provider_index.take(provider) else { return; };
+            let last = self.provider_count.saturating_sub(1);
+            if index != last {
+                if let Some(moved) = self.provider_at.get(last) {
+                    self.provider_at.insert(index, &moved);
+                    self.provider_index.insert(moved, &index);
+                }
+            }
+            self.provider_at.remove(last);
+        }
+
+        /// Return `amount` of a provider's bonded stake to the provider or the
+        /// treasury immediately, skipping unbonding, e.g. to recover funds for a
+        /// lost provider key. An active provider left below `min_stake` is deactivated.
+        fn force_withdraw(&mut self, provider: H160, amount: u128, to: H160) -> bool {
+            if to != provider && to != self.treasury { return false; }
+            let Some(original) = self.providers.get(provider) else { return false; };
+            if amount == 0 || original.stake < amount { return false; }
+
+            let mut profile = original.clone();
+            profile.stake = profile.stake.saturating_sub(amount);
+            let deactivated = profile.is_active && Self::bonded(&profile) < self.min_stake;
+            if deactivated { profile.is_active = false; }
+            self.providers.insert(provider, &profile);
+
+            if self.env().transfer(to, U256::from(amount)).is_err() {
+                self.providers.insert(provider, &original);
+                return false;
+            }
+
+            if deactivated {
+                self.env().emit_event(ProviderActiveChanged { provider, is_active: false });
+            }
+            self.env().emit_event(StakeForceWithdrawn { provider, to, amount });
+            true
+        }
+
+        /// Rate of an `Hourly` pricing model; `u128::MAX` for other models.
+        fn hourly_rate(profile: &ProviderProfile) -> u128 {
+            match profile.pricing {
+                PricingModel::Hourly { rate, .. } => rate,
+                _ => u128::MAX,
+            }
+        }
+
+        /// Own plus delegated stake.
+        fn bonded(profile: &ProviderProfile) -> u128 { profile.stake.saturating_add(profile.delegated_stake) }
+
+        /// Active and holding `min_stake`. A provider left below a raised
+        /// minimum keeps its `is_active` flag until `enforce_min_stake` clears
+        /// it, but is not offered work in the meantime.
+        fn is_eligible(&self, profile: &ProviderProfile) -> bool { profile.is_active && Self::bonded(profile) >= self.min_stake }
+
+        /// `a * b / c` without intermediate overflow; 0 when `c` is 0.
+        fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+            if c == 0 { return 0; }
+            let result = U256::from(a).saturating_mul(U256::from(b)) / U256::from(c);
+            if result > U256::from(u128::MAX) { u128::MAX } else { result.as_u128() }
+        }
+
+        /// `a * b / c` rounded up; 0 when `c` is 0.
+        fn mul_div_ceil(a: u128, b: u128, c: u128) -> u128 {
+            let floor = Self::mul_div(a, b, c);
+            if c != 0 && U256::from(a).saturating_mul(U256::from(b)) % U256::from(c) != U256::zero() { floor.saturating_add(1) } else { floor }
+        }
+
+        /// Apply a scheduled commission increase once it is due.
+        fn apply_pending_commission(&mut self, provider: H160, profile: &mut ProviderProfile) {
+            let Some((commission_bps, effective_at)) = self.pending_commissions.get(provider) else { return; };
+            if self.env().block_number() < effective_at { return; }
+            self.pending_commissions.remove(provider);
+            profile.commission_bps = commission_bps;
+            self.providers.insert(provider, profile);
+        }
+
+        /// Move rewards accrued since the last settlement into `pending_rewards`.
+        fn settle_rewards(pool: &DelegationPool, delegation: &mut Delegation) {
+            let accrued = Self::mul_div(delegation.shares, pool.reward_per_share, REWARD_PRECISION);
+            delegation.pending_rewards = delegation.pending_rewards.saturating_add(accrued.saturating_sub(delegation.reward_debt));
+            delegation.reward_debt = accrued;
+        }
+
+        /// Store a delegation, dropping it once it holds nothing.
+        fn store_delegation(&mut self, provider: H160, delegator: H160, delegation: Delegation) {
+            if delegation.shares == 0 && delegation.pending_rewards == 0 {
+                self.delegations.remove((provider, delegator));
+            } else {
+                self.delegations.insert((provider, delegator), &delegation);
+            }
+        }
+    }
+
+    #[ink(event)]
+    pub struct ProviderRegistered { #[ink(topic)] pub provider: H160, pub stake: u128, pub compute_units: u64 }
+    #[ink(event)]
+    pub struct ProviderUpdated { #[ink(topic)] pub provider: H160, pub endpoint: String, pub hourly_rate: u128 }
+    #[ink(event)]
+    pub struct IdentityKeyBound { #[ink(topic)] pub provider: H160, pub key: IdentityKey }
+    #[ink(event)]
+    pub struct ChallengeIssued { #[ink(topic)] pub provider: H160, pub challenge: [u8; 32], pub expires_at: u32 }
+    #[ink(event)]
+    pub struct EndpointVerified { #[ink(topic)] pub provider: H160, #[ink(topic)] pub verifier: H160 }
+    #[ink(event)]
+    pub struct PricingUpdated { #[ink(topic)] pub provider: H160, pub pricing: PricingModel }
+    #[ink(event)]
+    pub struct CapabilitiesUpdated { #[ink(topic)] pub provider: H160, pub capabilities: Capabilities }
+    #[ink(event)]
+    pub struct CapacityReserved { #[ink(topic)] pub provider: H160, #[ink(topic)] pub job_id: u128, pub units: u64 }
+    #[ink(event)]
+    pub struct CapacityReleased { #[ink(topic)] pub provider: H160, #[ink(topic)] pub job_id: u128, pub units: u64 }
+    #[ink(event)]
+    pub struct DeregistrationRequested { #[ink(topic)] pub provider: H160, pub deregister_at: u32 }
+    #[ink(event)]
+    pub struct ProviderDeregistered { #[ink(topic)] pub provider: H160 }
+    #[ink(event)]
+    pub struct ProviderSuspended { #[ink(topic)] pub provider: H160, pub suspended: bool }
+    #[ink(event)]
+    pub struct ProviderActiveChanged { #[ink(topic)] pub provider: H160, pub is_active: bool }
+    #[ink(event)]
+    pub struct StakeAdded { #[ink(topic)] pub provider: H160, pub amount: u128 }
+    #[ink(event)]
+    pub struct UnstakeRequested { #[ink(topic)] pub provider: H160, pub amount: u128, pub unlock_block: u32 }
+    #[ink(event)]
+    pub struct StakeWithdrawn { #[ink(topic)] pub provider: H160, pub amount: u128 }
+    #[ink(event)]
+    pub struct StakeForceWithdrawn { #[ink(topic)] pub provider: H160, #[ink(topic)] pub to: H160, pub amount: u128 }
+    #[ink(event)]
+    pub struct ReputationUpdated { #[ink(topic)] pub provider: H160, pub score: u32 }
+    #[ink(event)]
+    pub struct SlaTermsSet { #[ink(topic)] pub provider: H160, pub sla: Option<SlaTerms> }
+    #[ink(event)]
+    pub struct ProbeIssued { #[ink(topic)] pub provider: H160, #[ink(topic)] pub monitor: H160, pub nonce: [u8; 32] }
+    #[ink(event)]
+    pub struct LivenessRecorded { #[ink(topic)] pub provider: H160, #[ink(topic)] pub monitor: H160, pub passed: bool, pub uptime_bps: u32 }
+    #[ink(event)]
+    pub struct OutcomeRecorded { #[ink(topic)] pub provider: H160, #[ink(topic)] pub reporter: H160, pub outcome: JobOutcome, pub job_value: u128, pub score: u32 }
+    #[ink(event)]
+    pub struct Slashed { #[ink(topic)] pub provider: H160, #[ink(topic)] pub slasher: H160, pub amount: u128, pub from_delegators: u128, pub reason_code: u8, pub beneficiary: H160 }
+    #[ink(event)]
+    pub struct Delegated { #[ink(topic)] pub provider: H160, #[ink(topic)] pub delegator: H160, pub amount: u128, pub shares: u128 }
+    #[ink(event)]
+    pub struct Undelegated { #[ink(topic)] pub provider: H160, #[ink(topic)] pub delegator: H160, pub amount: u128, pub unlock_block: u32 }
+    #[ink(event)]
+    pub struct UndelegationWithdrawn { #[ink(topic)] pub provider: H160, #[ink(topic)] pub delegator: H160, pub amount: u128 }
+    #[ink(event)]
+    pub struct CommissionSet { #[ink(topic)] pub provider: H160, pub commission_bps: u16, pub effective_at: u32 }
+    #[ink(event)]
+    pub struct RewardsDistributed { #[ink(topic)] pub provider: H160, pub amount: u128, pub commission: u128 }
+    #[ink(event)]
+    pub struct RewardsClaimed { #[ink(topic)] pub provider: H160, #[ink(topic)] pub account: H160, pub amount: u128 }
+    #[ink(event)]
+    pub struct ChangeProposed { #[ink(topic)] pub id: u32, pub change: ParamChange, pub executable_at: u32 }
+    #[ink(event)]
+    pub struct ChangeExecuted { #[ink(topic)] pub id: u32, pub change: ParamChange }
+    #[ink(event)]
+    pub struct ChangeCancelled { #[ink(topic)] pub id: u32 }
+    #[ink(event)]
+    pub struct UnbondingPeriodSet { pub blocks: u32 }
+    #[ink(event)]
+    pub struct TreasurySet { #[ink(topic)] pub treasury: H160 }
+    #[ink(event)]
+    pub struct RoleGranted { pub role: Role, #[ink(topic)] pub account: H160 }
+    #[ink(event)]
+    pub struct RoleRevoked { pub role: Role, #[ink(topic)] pub account: H160 }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn alice() -> H160 { H160::from([0x1; 20]) }
+        fn bob() -> H160 { H160::from([0x2; 20]) }
+        fn charlie() -> H160 { H160::from([0x3; 20]) }
+
+        fn set_caller(account: H160) {
+            ink::env::test::set_caller(account.into());
+        }
+
+        fn set_value(amount: u128) {
+            ink::env::test::set_value_transferred(amount.into());
+        }
+
+        /// Alice deploys with a minimum stake of 1000 and Bob registers with
+        /// 2000 staked. Bob is left as the caller.
+        fn setup() -> ComputeProviderRegistry {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            set_caller(bob());
+            set_value(2000);
+            assert!(registry.register_provider("http://provider.com".to_string(), 100, 50u128));
+            registry
+        }
+
+        /// Grants `role` to `account` through the timelock and leaves `account` as the caller.
+        fn grant(registry: &mut ComputeProviderRegistry, role: Role, account: H160) {
+            enact(registry, ParamChange::GrantRole(role, account));
+            set_caller(account);
+        }
+
+        /// `delegator` delegates `amount` to Bob and is left as the caller.
+        fn delegate(registry: &mut ComputeProviderRegistry, delegator: H160, amount: u128) {
+            set_caller(delegator);
+            set_value(amount);
+            assert!(registry.delegate(bob()));
+        }
+
+        #[ink::test]
+        fn new_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let registry = ComputeProviderRegistry::new(min_stake);
+            
+            assert_eq!(registry.get_min_stake(), min_stake);
+            assert_eq!(registry.get_admin(), alice());
+            assert_eq!(registry.get_provider_count(), 0);
+        }
+
+        #[ink::test]
+        fn register_provider_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            
+            assert!(registry.register_provider(
+                "http://provider.com".to_string(),
+                100, // compute_units
+                50u128 // hourly_rate
+            ));
+            
+            assert_eq!(registry.get_provider_count(), 1);
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.provider, bob());
+            assert_eq!(profile.endpoint, "http://provider.com");
+            assert_eq!(profile.compute_units, 100);
+            assert_eq!(profile.hourly_rate, 50u128);
+            assert_eq!(profile.is_active, true);
+            assert_eq!(profile.stake, 1000u128);
+            assert_eq!(profile.reputation_score, 100);
+        }
+
+        #[ink::test]
+        fn register_provider_insufficient_stake_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(500); // Insufficient stake
+            
+            assert!(!registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            ));
+            
+            assert_eq!(registry.get_provider_count(), 0);
+            assert!(registry.get_provider(bob()).is_none());
+        }
+
+        #[ink::test]
+        fn register_provider_already_registered_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            
+            // First registration
+            assert!(registry.register_provider(
+                "http://provider1.com".to_string(),
+                100,
+                50u128
+            ));
+            
+            // Attempt second registration
+            set_value(1000);
+            assert!(!registry.register_provider(
+                "http://provider2.com".to_string(),
+                200,
+                75u128
+            ));
+            
+            // Original data should remain unchanged
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.endpoint, "http://provider1.com");
+            assert_eq!(profile.compute_units, 100);
+        }
+
+        #[ink::test]
+        fn update_provider_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            registry.register_provider(
+                "http://old.com".to_string(),
+                100,
+                50u128
+            );
+            
+            assert!(registry.update_provider(
+                "http://new.com".to_string(),
+                75u128
+            ));
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.endpoint, "http://new.com");
+            assert_eq!(profile.hourly_rate, 75u128);
+            assert_eq!(profile.compute_units, 100); // Unchanged
+        }
+
+        #[ink::test]
+        fn update_provider_not_registered_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            assert!(!registry.update_provider(
+                "http://new.com".to_string(),
+                75u128
+            ));
+        }
+
+        #[ink::test]
+        fn set_active_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            // Provider starts as active
+            assert_eq!(registry.get_provider(bob()).unwrap().is_active, true);
+            
+            // Set inactive
+            assert!(registry.set_active(false));
+            assert_eq!(registry.get_provider(bob()).unwrap().is_active, false);
+            
+            // Set active again
+            assert!(registry.set_active(true));
+            assert_eq!(registry.get_provider(bob()).unwrap().is_active, true);
+        }
+
+        #[ink::test]
+        fn set_active_not_registered_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            assert!(!registry.set_active(false));
+        }
+
+        #[ink::test]
+        fn add_stake_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            set_value(500);
+            assert!(registry.add_stake());
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 1500u128);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Nothing staked")]
+        fn add_stake_zero_amount_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            set_value(0);
+            registry.add_stake();
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Not a provider")]
+        fn add_stake_not_registered_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(500);
+            registry.add_stake();
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Provider is deregistering")]
+        fn add_stake_while_deregistering_fails() {
+            let mut registry = setup();
+            set_caller(bob());
+            assert!(registry.deregister());
+
+            set_value(500);
+            registry.add_stake();
+        }
+
+        #[ink::test]
+        fn withdraw_stake_by_inactive_provider_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(2000);
+            registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            // Set provider inactive
+            registry.set_active(false);
+            
+            // Withdraw partial stake
+            assert!(registry.withdraw_stake(500u128));
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 1500u128);
+        }
+
+        #[ink::test]
+        fn withdraw_stake_by_active_admin_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            // Admin registers as a provider
+            set_value(2000);
+            registry.register_provider(
+                "http://admin-provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            // Being admin grants no exemption for the admin's own profile
+            assert!(!registry.withdraw_stake(500u128));
+            
+            let profile = registry.get_provider(alice()).unwrap();
+            assert_eq!(profile.stake, 2000u128);
+        }
+
+        #[ink::test]
+        fn withdraw_stake_active_provider_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(2000);
+            registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            // Active provider cannot withdraw stake
+            assert!(!registry.withdraw_stake(500u128));
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 2000u128);
+        }
+
+        #[ink::test]
+        fn withdraw_stake_insufficient_balance_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            registry.set_active(false);
+            
+            // Try to withdraw more than staked
+            assert!(!registry.withdraw_stake(1500u128));
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 1000u128);
+        }
+
+        #[ink::test]
+        fn reputation_change_by_admin_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
+            registry.register_provider(
+                "http://provider.com".to_string(),
+                100,
+                50u128
+            );
+            
+            enact(&mut registry, ParamChange::Reputation { provider: bob(), score: 85 });
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.reputation_score, 85);
+
+            // Scores are bounded
+            let too_high = ParamChange::Reputation { provider: bob(), score: MAX_REPUTATION + 1 };
+            assert_eq!(registry.propose_change(too_high), None);
+        }
+
+        #[ink::test]
+        fn reputation_change_not_admin_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            set_value(1000);
             registry.register_provider(
                 "http://provider.com".to_string(),
                 100,
                 50u128
-            );
+            );
+            
+            // Non-admin trying to set reputation
+            set_caller(charlie());
+            assert_eq!(registry.propose_change(ParamChange::Reputation { provider: bob(), score: 85 }), None);
+            
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.reputation_score, 100); // Unchanged
+        }
+
+        #[ink::test]
+        fn reputation_change_nonexistent_provider_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            let id = registry.propose_change(ParamChange::Reputation { provider: bob(), score: 85 }).unwrap();
+            set_block(GOVERNANCE_DELAY);
+            assert!(!registry.execute_change(id));
+            assert!(registry.get_pending_change(id).is_some());
+        }
+
+        #[ink::test]
+        fn min_stake_change_by_admin_works() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            enact(&mut registry, ParamChange::MinStake(2000u128));
+            assert_eq!(registry.get_min_stake(), 2000u128);
+        }
+
+        #[ink::test]
+        fn min_stake_increase_sidelines_providers_below_it() {
+            let mut registry = setup();
+            grant(&mut registry, Role::JobManager, alice());
+            set_caller(charlie());
+            set_value(3000);
+            assert!(registry.register_provider("http://charlie.com".to_string(), 100, 50u128));
+
+            enact(&mut registry, ParamChange::MinStake(2500u128));
+            assert!(registry.get_provider(bob()).unwrap().is_active);
+
+            // Bob is no longer offered work, though his flag is still set
+            assert_eq!(addresses(registry.find_providers(0, u128::MAX, 0, false)), vec![charlie()]);
+            assert_eq!(registry.available_units(bob()), 0);
+            set_caller(alice());
+            assert!(!registry.reserve_capacity(bob(), 10, 1));
+            assert!(registry.reserve_capacity(charlie(), 10, 1));
+
+            // Bob needs more stake before coming back
+            set_caller(bob());
+            assert!(registry.set_active(false));
+            assert!(!registry.set_active(true));
+        }
+
+        #[ink::test]
+        fn enforce_min_stake_deactivates_page_by_page() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, bob(), 100, 50);
+            register(&mut registry, charlie(), 100, 50);
+            register(&mut registry, dave(), 100, 50);
+            set_caller(charlie());
+            set_value(2000);
+            assert!(registry.add_stake());
+
+            enact(&mut registry, ParamChange::MinStake(2500u128));
+
+            // Anyone can apply the new minimum; the first page holds bob and charlie
+            set_caller(eve());
+            assert_eq!(registry.enforce_min_stake(0, 2), 1);
+            assert!(!registry.get_provider(bob()).unwrap().is_active);
+            assert!(registry.get_provider(charlie()).unwrap().is_active);
+            assert!(registry.get_provider(dave()).unwrap().is_active);
+
+            assert_eq!(registry.enforce_min_stake(2, 2), 1);
+            assert!(!registry.get_provider(dave()).unwrap().is_active);
+            // Nothing left to deactivate, and past the end is a no-op
+            assert_eq!(registry.enforce_min_stake(0, 50), 0);
+            assert_eq!(registry.enforce_min_stake(10, 50), 0);
+        }
+
+        #[ink::test]
+        fn min_stake_change_not_admin_fails() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            set_caller(bob());
+            assert_eq!(registry.propose_change(ParamChange::MinStake(2000u128)), None);
+            assert_eq!(registry.get_min_stake(), 1000u128);
+        }
+
+        #[ink::test]
+        fn get_provider_nonexistent() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let registry = ComputeProviderRegistry::new(min_stake);
+            
+            assert!(registry.get_provider(bob()).is_none());
+        }
+
+        #[ink::test]
+        fn multiple_providers_registration() {
+            set_caller(alice());
+            let min_stake = 1000u128;
+            let mut registry = ComputeProviderRegistry::new(min_stake);
+            
+            // Register first provider
+            set_caller(bob());
+            set_value(1000);
+            assert!(registry.register_provider(
+                "http://bob.com".to_string(),
+                100,
+                50u128
+            ));
             
-            // Provider starts as active
-            assert_eq!(registry.get_provider(bob()).unwrap().is_active, true);
+            // Register second provider
+            set_caller(charlie());
+            set_value(1500);
+            assert!(registry.register_provider(
+                "http://charlie.com".to_string(),
+                200,
+                75u128
+            ));
             
-            // Set inactive
+            assert_eq!(registry.get_provider_count(), 2);
+            
+            let bob_profile = registry.get_provider(bob()).unwrap();
+            let charlie_profile = registry.get_provider(charlie()).unwrap();
+            
+            assert_eq!(bob_profile.endpoint, "http://bob.com");
+            assert_eq!(bob_profile.stake, 1000u128);
+            assert_eq!(charlie_profile.endpoint, "http://charlie.com");
+            assert_eq!(charlie_profile.stake, 1500u128);
+        }
+
+        /// Proposes `change` as the admin (Alice) and executes it after the delay.
+        fn enact(registry: &mut ComputeProviderRegistry, change: ParamChange) {
+            set_caller(alice());
+            let id = registry.propose_change(change).unwrap();
+            set_block(ink::env::block_number::<ink::env::DefaultEnvironment>() + GOVERNANCE_DELAY);
+            assert!(registry.execute_change(id));
+        }
+
+        #[ink::test]
+        fn grant_and_revoke_role_works() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            assert!(!registry.has_role(Role::Reporter, charlie()));
+            enact(&mut registry, ParamChange::GrantRole(Role::Reporter, charlie()));
+            assert!(registry.has_role(Role::Reporter, charlie()));
+
+            enact(&mut registry, ParamChange::RevokeRole(Role::Reporter, charlie()));
+            assert!(!registry.has_role(Role::Reporter, charlie()));
+        }
+
+        #[ink::test]
+        fn roles_require_timelock() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            for role in [Role::Slasher, Role::Reporter, Role::JobManager, Role::Verifier, Role::Monitor] {
+                let id = registry.propose_change(ParamChange::GrantRole(role, charlie())).unwrap();
+                assert!(!registry.execute_change(id));
+                assert!(!registry.has_role(role, charlie()));
+            }
+
+            set_block(GOVERNANCE_DELAY);
+            for id in 0..5 {
+                assert!(registry.execute_change(id));
+            }
+            assert!(registry.has_role(Role::Monitor, charlie()));
+        }
+
+        #[ink::test]
+        fn grant_role_not_admin_fails() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            set_caller(bob());
+            assert_eq!(registry.propose_change(ParamChange::GrantRole(Role::Reporter, bob())), None);
+            assert!(!registry.has_role(Role::Reporter, bob()));
+        }
+
+        #[ink::test]
+        fn treasury_change_works() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            assert_eq!(registry.get_treasury(), alice());
+
+            enact(&mut registry, ParamChange::Treasury(charlie()));
+            assert_eq!(registry.get_treasury(), charlie());
+
+            set_caller(bob());
+            assert_eq!(registry.propose_change(ParamChange::Treasury(bob())), None);
+            assert_eq!(registry.get_treasury(), charlie());
+        }
+
+        #[ink::test]
+        fn slash_by_slasher_works() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, charlie());
+
+            set_caller(charlie());
+            assert!(registry.slash(bob(), 500, 1, Some(alice())));
+
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 1500u128);
+            assert!(profile.is_active);
+        }
+
+        #[ink::test]
+        fn slash_not_slasher_fails() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, charlie());
+
+            // Even the admin needs the role
+            set_caller(alice());
+            assert!(!registry.slash(bob(), 500, 1, None));
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 2000u128);
+        }
+
+        #[ink::test]
+        fn slash_capped_at_stake() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, charlie());
+
+            set_caller(charlie());
+            assert!(registry.slash(bob(), 5000, 1, None));
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 0);
+
+            // Nothing left to slash
+            assert!(!registry.slash(bob(), 1, 1, None));
+        }
+
+        #[ink::test]
+        fn slash_below_min_stake_deactivates() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, charlie());
+
+            set_caller(charlie());
+            assert!(registry.slash(bob(), 1001, 2, None));
+
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 999u128);
+            assert!(!profile.is_active);
+        }
+
+        #[ink::test]
+        fn slash_unknown_provider_fails() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, charlie());
+
+            set_caller(charlie());
+            assert!(!registry.slash(alice(), 100, 1, None));
+        }
+
+        fn set_block(block: u32) {
+            ink::env::test::set_block_number::<ink::env::DefaultEnvironment>(block);
+        }
+
+        #[ink::test]
+        fn request_unstake_queues_entry() {
+            let mut registry = setup();
+            set_block(5);
+
+            assert!(registry.request_unstake(400));
+
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 1600u128);
+            assert_eq!(registry.get_unbonding(bob()), vec![UnbondingEntry { amount: 400, unlock_block: 5 + DEFAULT_UNBONDING_PERIOD }]);
+        }
+
+        #[ink::test]
+        fn request_unstake_keeps_min_stake_while_active() {
+            let mut registry = setup();
+
+            assert!(!registry.request_unstake(1001));
+            assert!(registry.request_unstake(1000));
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 1000u128);
+
+            // Inactive providers may unbond everything
+            registry.set_active(false);
+            assert!(registry.request_unstake(1000));
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 0);
+        }
+
+        #[ink::test]
+        fn withdraw_unbonded_waits_for_unlock() {
+            let mut registry = setup();
+            set_block(1);
+            assert!(registry.request_unstake(300));
+            set_block(6);
+            assert!(registry.request_unstake(200));
+
+            set_block(DEFAULT_UNBONDING_PERIOD);
+            assert!(!registry.withdraw_unbonded());
+
+            set_block(1 + DEFAULT_UNBONDING_PERIOD);
+            assert!(registry.withdraw_unbonded());
+            assert_eq!(registry.get_unbonding(bob()), vec![UnbondingEntry { amount: 200, unlock_block: 6 + DEFAULT_UNBONDING_PERIOD }]);
+
+            set_block(6 + DEFAULT_UNBONDING_PERIOD);
+            assert!(registry.withdraw_unbonded());
+            assert!(registry.get_unbonding(bob()).is_empty());
+            assert!(!registry.withdraw_unbonded());
+        }
+
+        #[ink::test]
+        fn withdraw_stake_enters_unbonding() {
+            let mut registry = setup();
+            registry.set_active(false);
+
+            assert!(registry.withdraw_stake(500));
+            assert_eq!(registry.get_unbonding(bob()).len(), 1);
+            // Not paid out before the period ends
+            assert!(!registry.withdraw_unbonded());
+        }
+
+        #[ink::test]
+        fn slash_reaches_unbonding_stake() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, charlie());
+
+            set_caller(bob());
+            registry.set_active(false);
+            assert!(registry.request_unstake(1500));
+            assert!(registry.request_unstake(400));
+
+            set_caller(charlie());
+            assert!(registry.slash(bob(), 600, 1, None));
+
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 0);
+            // The latest entry is consumed first
+            let unlock_block = GOVERNANCE_DELAY + DEFAULT_UNBONDING_PERIOD;
+            assert_eq!(registry.get_unbonding(bob()), vec![UnbondingEntry { amount: 1400, unlock_block }]);
+        }
+
+        #[ink::test]
+        fn unbonding_period_change_validates() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            assert_eq!(registry.get_unbonding_period(), DEFAULT_UNBONDING_PERIOD);
+
+            assert_eq!(registry.propose_change(ParamChange::UnbondingPeriod(0)), None);
+            assert_eq!(registry.propose_change(ParamChange::UnbondingPeriod(MIN_UNBONDING_PERIOD - 1)), None);
+            set_caller(bob());
+            assert_eq!(registry.propose_change(ParamChange::UnbondingPeriod(MIN_UNBONDING_PERIOD)), None);
+
+            enact(&mut registry, ParamChange::UnbondingPeriod(MIN_UNBONDING_PERIOD));
+            assert_eq!(registry.get_unbonding_period(), MIN_UNBONDING_PERIOD);
+        }
+
+        #[ink::test]
+        fn force_withdraw_by_admin_works() {
+            let mut registry = setup();
+
+            enact(&mut registry, ParamChange::ForceWithdraw { provider: bob(), amount: 1000, to: bob() });
+
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 1000u128);
+            assert!(profile.is_active);
+            assert!(registry.get_unbonding(bob()).is_empty());
+        }
+
+        #[ink::test]
+        fn force_withdraw_below_min_stake_deactivates() {
+            let mut registry = setup();
+
+            // The treasury (Alice by default) is the only other recipient
+            enact(&mut registry, ParamChange::ForceWithdraw { provider: bob(), amount: 1500, to: alice() });
+
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 500u128);
+            assert!(!profile.is_active);
+        }
+
+        #[ink::test]
+        fn force_withdraw_not_admin_fails() {
+            let mut registry = setup();
+
+            set_caller(bob());
+            assert_eq!(registry.propose_change(ParamChange::ForceWithdraw { provider: bob(), amount: 500, to: bob() }), None);
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 2000u128);
+        }
+
+        #[ink::test]
+        fn force_withdraw_only_to_provider_or_treasury() {
+            let mut registry = setup();
+
+            set_caller(alice());
+            assert_eq!(registry.propose_change(ParamChange::ForceWithdraw { provider: bob(), amount: 500, to: charlie() }), None);
+            assert_eq!(registry.propose_change(ParamChange::ForceWithdraw { provider: bob(), amount: 0, to: bob() }), None);
+
+            // The treasury moving away before execution blocks the payout
+            let id = registry.propose_change(ParamChange::ForceWithdraw { provider: bob(), amount: 500, to: alice() }).unwrap();
+            let treasury = registry.propose_change(ParamChange::Treasury(charlie())).unwrap();
+            set_block(GOVERNANCE_DELAY);
+            assert!(registry.execute_change(treasury));
+            assert!(!registry.execute_change(id));
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 2000u128);
+        }
+
+        #[ink::test]
+        fn force_withdraw_more_than_stake_stays_queued() {
+            let mut registry = setup();
+
+            set_caller(alice());
+            let id = registry.propose_change(ParamChange::ForceWithdraw { provider: bob(), amount: 2001, to: bob() }).unwrap();
+            set_block(GOVERNANCE_DELAY);
+            assert!(!registry.execute_change(id));
+            assert!(registry.get_pending_change(id).is_some());
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 2000u128);
+
+            // Bob tops up, and the withdrawal goes through
+            set_caller(bob());
+            set_value(1);
+            assert!(registry.add_stake());
+            assert!(registry.execute_change(id));
+            assert!(registry.get_pending_change(id).is_none());
+            assert_eq!(registry.get_provider(bob()).unwrap().stake, 0);
+        }
+
+        #[ink::test]
+        fn set_active_below_min_stake_fails() {
+            let mut registry = setup();
+
+            set_caller(bob());
+            registry.set_active(false);
+            assert!(registry.withdraw_stake(1500));
+
+            assert!(!registry.set_active(true));
+            assert!(!registry.get_provider(bob()).unwrap().is_active);
+        }
+
+        fn gpu_capabilities() -> Capabilities {
+            Capabilities {
+                hardware_class: HardwareClass::Gpu,
+                accelerator_type: "A100".to_string(),
+                accelerator_count: 4,
+                memory_gb: 80,
+                tee_supported: true,
+                tee_type: Some(TeeType::Phala),
+                frameworks: vec!["pytorch".to_string(), "onnx".to_string()],
+                region: "eu-west".to_string(),
+            }
+        }
+
+        #[ink::test]
+        fn register_provider_has_default_capabilities() {
+            let registry = setup();
+            assert_eq!(registry.get_provider(bob()).unwrap().capabilities, Capabilities::default());
+        }
+
+        #[ink::test]
+        fn register_provider_with_capabilities_works() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            set_caller(bob());
+            set_value(1000);
+            assert!(registry.register_provider_with_capabilities("http://gpu.com".to_string(), 400, 90u128, gpu_capabilities()));
+
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.capabilities, gpu_capabilities());
+            assert_eq!(profile.compute_units, 400);
+        }
+
+        #[ink::test]
+        fn register_provider_with_invalid_capabilities_fails() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            let mut capabilities = gpu_capabilities();
+            capabilities.tee_supported = false;
+
+            set_caller(bob());
+            set_value(1000);
+            assert!(!registry.register_provider_with_capabilities("http://gpu.com".to_string(), 400, 90u128, capabilities));
+            assert!(registry.get_provider(bob()).is_none());
+            assert_eq!(registry.get_provider_count(), 0);
+        }
+
+        #[ink::test]
+        fn update_capabilities_works() {
+            let mut registry = setup();
+
+            set_caller(bob());
+            assert!(registry.update_capabilities(gpu_capabilities()));
+            assert_eq!(registry.get_provider(bob()).unwrap().capabilities, gpu_capabilities());
+
+            let mut too_many = gpu_capabilities();
+            too_many.frameworks = vec!["f".to_string(); MAX_FRAMEWORKS + 1];
+            assert!(!registry.update_capabilities(too_many));
+
+            let mut long_region = gpu_capabilities();
+            long_region.region = "x".repeat(MAX_DESCRIPTOR_LEN + 1);
+            assert!(!registry.update_capabilities(long_region));
+            assert_eq!(registry.get_provider(bob()).unwrap().capabilities, gpu_capabilities());
+        }
+
+        #[ink::test]
+        fn update_capabilities_not_registered_fails() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            set_caller(bob());
+            assert!(!registry.update_capabilities(gpu_capabilities()));
+        }
+
+        fn dave() -> H160 { H160::from([0x4; 20]) }
+        fn eve() -> H160 { H160::from([0x5; 20]) }
+
+        fn register(registry: &mut ComputeProviderRegistry, provider: H160, compute_units: u64, hourly_rate: u128) {
+            set_caller(provider);
+            set_value(1000);
+            assert!(registry.register_provider("http://provider.com".to_string(), compute_units, hourly_rate));
+        }
+
+        fn addresses(profiles: Vec<ProviderProfile>) -> Vec<H160> {
+            profiles.into_iter().map(|p| p.provider).collect()
+        }
+
+        #[ink::test]
+        fn list_providers_paginates() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, bob(), 100, 50);
+            register(&mut registry, charlie(), 100, 50);
+            register(&mut registry, dave(), 100, 50);
+
+            assert_eq!(addresses(registry.list_providers(0, 2)), vec![bob(), charlie()]);
+            assert_eq!(addresses(registry.list_providers(2, 2)), vec![dave()]);
+            assert!(registry.list_providers(3, 2).is_empty());
+            assert!(registry.list_providers(0, 0).is_empty());
+        }
+
+        #[ink::test]
+        fn find_providers_filters_and_sorts() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, bob(), 100, 80);
+            register(&mut registry, charlie(), 200, 40);
+            register(&mut registry, dave(), 400, 60);
+
+            enact(&mut registry, ParamChange::Reputation { provider: dave(), score: 300 });
+
+            // Reputation first, then the cheaper of the two tied at 100
+            assert_eq!(addresses(registry.find_providers(0, u128::MAX, 0, false)), vec![dave(), charlie(), bob()]);
+            assert_eq!(addresses(registry.find_providers(150, u128::MAX, 0, false)), vec![dave(), charlie()]);
+            assert_eq!(addresses(registry.find_providers(0, 70, 0, false)), vec![dave(), charlie()]);
+            assert_eq!(addresses(registry.find_providers(0, u128::MAX, 200, false)), vec![dave()]);
+        }
+
+        #[ink::test]
+        fn find_providers_filters_on_pricing_model() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, bob(), 100, 80);
+            register(&mut registry, charlie(), 100, 40);
+
+            // Charlie's old hourly rate no longer describes its price
+            set_caller(charlie());
+            assert!(registry.update_pricing(PricingModel::PerUnit { unit: "1k tokens".to_string(), price: 1 }));
+            assert_eq!(registry.get_provider(charlie()).unwrap().hourly_rate, 40);
+
+            assert_eq!(addresses(registry.find_providers(0, 100, 0, false)), vec![bob()]);
+            assert!(registry.find_providers(0, 50, 0, false).is_empty());
+            // Without a rate limit every model matches, hourly first
+            assert_eq!(addresses(registry.find_providers(0, u128::MAX, 0, false)), vec![bob(), charlie()]);
+        }
+
+        #[ink::test]
+        fn find_providers_skips_inactive_and_requires_tee() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, bob(), 100, 50);
+            register(&mut registry, charlie(), 100, 50);
+
+            set_caller(charlie());
+            assert!(registry.update_capabilities(gpu_capabilities()));
+            set_caller(bob());
+            assert!(registry.set_active(false));
+
+            assert_eq!(addresses(registry.find_providers(0, u128::MAX, 0, false)), vec![charlie()]);
+            assert_eq!(addresses(registry.find_providers(0, u128::MAX, 0, true)), vec![charlie()]);
+
+            set_caller(charlie());
+            assert!(registry.update_capabilities(Capabilities::default()));
+            assert!(registry.find_providers(0, u128::MAX, 0, true).is_empty());
+        }
+
+        #[ink::test]
+        fn record_outcome_updates_counters_and_score() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Reporter, charlie());
+
+            // 100 + (1000 - 100) * 5%
+            assert!(registry.record_outcome(bob(), JobOutcome::Completed, 0));
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.jobs_completed, 1);
+            assert_eq!(profile.reputation_score, 145);
+
+            // 145 -> 138 (failed) -> 132 (disputed) -> 150 (late, target 500)
+            assert!(registry.record_outcome(bob(), JobOutcome::Failed, 0));
+            assert!(registry.record_outcome(bob(), JobOutcome::Disputed, 0));
+            assert!(registry.record_outcome(bob(), JobOutcome::Late, 0));
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!((profile.jobs_failed, profile.jobs_disputed, profile.jobs_late), (1, 1, 1));
+            assert_eq!(profile.reputation_score, 150);
+        }
+
+        #[ink::test]
+        fn record_outcome_weighs_job_value() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Reporter, charlie());
+
+            // Three full value steps: 4 * 5% = 20% of the distance
+            assert!(registry.record_outcome(bob(), JobOutcome::Completed, 3_000_000_000_000));
+            assert_eq!(registry.get_provider(bob()).unwrap().reputation_score, 280);
+
+            // Weight is capped at 100%
+            assert!(registry.record_outcome(bob(), JobOutcome::Failed, u128::MAX));
+            assert_eq!(registry.get_provider(bob()).unwrap().reputation_score, 0);
+        }
+
+        #[ink::test]
+        fn record_outcome_not_reporter_fails() {
+            let mut registry = setup();
+
+            set_caller(alice());
+            assert!(!registry.record_outcome(bob(), JobOutcome::Completed, 0));
+            assert_eq!(registry.get_provider(bob()).unwrap().jobs_completed, 0);
+        }
+
+        #[ink::test]
+        fn reputation_weights_change_validates() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            let weights = ReputationWeights { smoothing_bps: 1_000, value_step: 0, ..ReputationWeights::default() };
+            enact(&mut registry, ParamChange::ReputationWeights(weights.clone()));
+            assert_eq!(registry.get_reputation_weights(), weights);
+
+            let too_smooth = ReputationWeights { smoothing_bps: 10_001, ..weights.clone() };
+            assert_eq!(registry.propose_change(ParamChange::ReputationWeights(too_smooth)), None);
+            let too_high = ReputationWeights { completed_target: MAX_REPUTATION + 1, ..weights.clone() };
+            assert_eq!(registry.propose_change(ParamChange::ReputationWeights(too_high)), None);
+
+            set_caller(bob());
+            assert_eq!(registry.propose_change(ParamChange::ReputationWeights(ReputationWeights::default())), None);
+            assert_eq!(registry.get_reputation_weights(), weights);
+        }
+
+        #[ink::test]
+        fn delegate_works() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+
+            set_caller(dave());
+            set_value(3000);
+            assert!(registry.delegate(bob()));
+
+            assert_eq!(registry.get_provider(bob()).unwrap().delegated_stake, 4000u128);
+            assert_eq!(registry.get_delegation_pool(bob()).total_shares, 4000u128);
+            assert_eq!(registry.get_delegated_value(bob(), charlie()), 1000u128);
+            assert_eq!(registry.get_delegated_value(bob(), dave()), 3000u128);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Unknown provider")]
+        fn delegate_to_unknown_provider_fails() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            set_caller(charlie());
+            set_value(1000);
+            registry.delegate(bob());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Nothing delegated")]
+        fn delegate_nothing_fails() {
+            let mut registry = setup();
+            set_caller(charlie());
+            set_value(0);
+            registry.delegate(bob());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Provider is deregistering")]
+        fn delegate_to_deregistering_provider_fails() {
+            let mut registry = setup();
+            set_caller(bob());
+            assert!(registry.deregister());
+
+            set_caller(charlie());
+            set_value(1000);
+            registry.delegate(bob());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Unknown provider")]
+        fn distribute_rewards_to_unknown_provider_fails() {
+            let mut registry = setup();
+            set_caller(charlie());
+            set_value(1000);
+            registry.distribute_rewards(dave());
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Nothing distributed")]
+        fn distribute_nothing_fails() {
+            let mut registry = setup();
+            set_caller(charlie());
+            set_value(0);
+            registry.distribute_rewards(bob());
+        }
+
+        #[ink::test]
+        fn delegated_stake_counts_toward_min_stake() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, bob(), 100, 50);
+
+            // Bob may unbond own stake that delegations cover
+            set_caller(charlie());
+            set_value(600);
+            assert!(registry.delegate(bob()));
+            set_caller(bob());
+            assert!(registry.request_unstake(600));
+            assert!(!registry.request_unstake(1));
+            assert!(registry.get_provider(bob()).unwrap().is_active);
+
+            // Pulling the delegation drops Bob below the minimum
+            set_caller(charlie());
+            assert!(registry.undelegate(bob(), 600));
+            assert!(!registry.get_provider(bob()).unwrap().is_active);
+        }
+
+        #[ink::test]
+        fn slash_splits_pro_rata_with_delegators() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+            grant(&mut registry, Role::Slasher, alice());
+
+            // Bob has 2000 own and 1000 delegated stake
+            assert!(registry.slash(bob(), 300, 1, None));
+
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.stake, 1800u128);
+            assert_eq!(profile.delegated_stake, 900u128);
+            assert_eq!(registry.get_delegated_value(bob(), charlie()), 900u128);
+        }
+
+        #[ink::test]
+        #[should_panic(expected = "Delegation pool slashed to zero")]
+        fn delegate_to_pool_slashed_to_zero_fails() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+            grant(&mut registry, Role::Slasher, alice());
+            assert!(registry.slash(bob(), 3000, 1, None));
+            assert_eq!(registry.get_provider(bob()).unwrap().delegated_stake, 0);
+
+            delegate(&mut registry, dave(), 1000);
+        }
+
+        #[ink::test]
+        fn undelegate_enters_unbonding() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+
+            assert!(!registry.undelegate(bob(), 1001));
+            assert!(registry.undelegate(bob(), 400));
+            assert_eq!(registry.get_delegated_value(bob(), charlie()), 600u128);
+            assert_eq!(registry.get_undelegating(bob(), charlie()), vec![UnbondingEntry { amount: 400, unlock_block: DEFAULT_UNBONDING_PERIOD }]);
+
+            assert!(!registry.withdraw_undelegated(bob()));
+            set_block(DEFAULT_UNBONDING_PERIOD);
+            // Undelegated stake is not paid out as own stake
+            assert!(!registry.withdraw_unbonded());
+            assert!(registry.withdraw_undelegated(bob()));
+            assert!(registry.get_undelegating(bob(), charlie()).is_empty());
+            assert_eq!(registry.get_delegation_pool(bob()).unbonding, 0);
+
+            assert!(registry.undelegate(bob(), 600));
+            assert_eq!(registry.get_delegation(bob(), charlie()), None);
+            assert_eq!(registry.get_provider(bob()).unwrap().delegated_stake, 0);
+        }
+
+        #[ink::test]
+        fn rewards_split_by_commission_and_shares() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+            set_caller(dave());
+            set_value(3000);
+            assert!(registry.delegate(bob()));
+
+            set_caller(bob());
+            assert!(registry.set_commission(1_000));
+            assert!(!registry.set_commission(10_001));
+            set_block(COMMISSION_INCREASE_DELAY);
+
+            set_caller(alice());
+            set_value(10_000);
+            assert!(registry.distribute_rewards(bob()));
+
+            assert_eq!(registry.get_pending_rewards(bob(), bob()), 1000u128);
+            assert_eq!(registry.get_pending_rewards(bob(), charlie()), 2250u128);
+            assert_eq!(registry.get_pending_rewards(bob(), dave()), 6750u128);
+
+            set_caller(charlie());
+            assert!(registry.claim_rewards(bob()));
+            assert_eq!(registry.get_pending_rewards(bob(), charlie()), 0);
+            assert!(!registry.claim_rewards(bob()));
+
+            set_caller(bob());
+            assert!(registry.claim_rewards(bob()));
+            assert_eq!(registry.get_pending_rewards(bob(), bob()), 0);
+        }
+
+        #[ink::test]
+        fn commission_increase_is_delayed() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+
+            set_caller(bob());
+            set_block(10);
+            assert!(registry.set_commission(5_000));
+            assert_eq!(registry.get_pending_commission(bob()), Some((5_000, 10 + COMMISSION_INCREASE_DELAY)));
+            assert_eq!(registry.get_provider(bob()).unwrap().commission_bps, 0);
+
+            // Rewards before the increase is due use the old commission
+            set_caller(alice());
+            set_value(1000);
+            assert!(registry.distribute_rewards(bob()));
+            assert_eq!(registry.get_pending_rewards(bob(), charlie()), 1000u128);
+
+            set_block(10 + COMMISSION_INCREASE_DELAY);
+            assert!(registry.distribute_rewards(bob()));
+            assert_eq!(registry.get_pending_rewards(bob(), bob()), 500u128);
+            assert_eq!(registry.get_provider(bob()).unwrap().commission_bps, 5_000);
+            assert_eq!(registry.get_pending_commission(bob()), None);
+
+            // Decreases apply at once and drop any scheduled increase
+            set_caller(bob());
+            assert!(registry.set_commission(9_000));
+            assert!(registry.set_commission(1_000));
+            assert_eq!(registry.get_provider(bob()).unwrap().commission_bps, 1_000);
+            assert_eq!(registry.get_pending_commission(bob()), None);
+        }
+
+        #[ink::test]
+        fn slash_reaches_undelegated_stake_pro_rata() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, alice());
+            delegate(&mut registry, charlie(), 1000);
+            assert!(registry.undelegate(bob(), 1000));
+            set_caller(bob());
             assert!(registry.set_active(false));
-            assert_eq!(registry.get_provider(bob()).unwrap().is_active, false);
-            
-            // Set active again
+            assert!(registry.request_unstake(2000));
+
+            // 2000 own and 1000 undelegated stake unbonding: a third each way
+            set_caller(alice());
+            assert!(registry.slash(bob(), 600, 1, None));
+            let unlock_block = GOVERNANCE_DELAY + DEFAULT_UNBONDING_PERIOD;
+            assert_eq!(registry.get_unbonding(bob()), vec![UnbondingEntry { amount: 1600, unlock_block }]);
+            assert_eq!(registry.get_undelegating(bob(), charlie()), vec![UnbondingEntry { amount: 800, unlock_block }]);
+
+            set_block(unlock_block);
+            set_caller(charlie());
+            assert!(registry.withdraw_undelegated(bob()));
+            assert!(registry.get_undelegating(bob(), charlie()).is_empty());
+        }
+
+        #[ink::test]
+        fn undelegation_slashed_to_zero_pays_nothing() {
+            let mut registry = setup();
+            grant(&mut registry, Role::Slasher, alice());
+            delegate(&mut registry, charlie(), 1000);
+            assert!(registry.undelegate(bob(), 1000));
+
+            // Takes all bonded stake, then all unbonding stake
+            set_caller(alice());
+            assert!(registry.slash(bob(), 4000, 1, None));
+            let unlock_block = GOVERNANCE_DELAY + DEFAULT_UNBONDING_PERIOD;
+            assert_eq!(registry.get_undelegating(bob(), charlie()), vec![UnbondingEntry { amount: 0, unlock_block }]);
+
+            set_block(unlock_block);
+            set_caller(charlie());
+            assert!(registry.withdraw_undelegated(bob()));
+            assert!(registry.get_undelegating(bob(), charlie()).is_empty());
+            assert!(!registry.withdraw_undelegated(bob()));
+        }
+
+        #[ink::test]
+        fn rewards_without_delegators_go_to_provider() {
+            let mut registry = setup();
+
+            set_caller(alice());
+            set_value(500);
+            assert!(registry.distribute_rewards(bob()));
+            assert_eq!(registry.get_pending_rewards(bob(), bob()), 500u128);
+        }
+
+        #[ink::test]
+        fn late_delegator_earns_only_later_rewards() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+
+            set_caller(alice());
+            set_value(1000);
+            assert!(registry.distribute_rewards(bob()));
+
+            set_caller(dave());
+            set_value(1000);
+            assert!(registry.delegate(bob()));
+
+            set_caller(alice());
+            set_value(1000);
+            assert!(registry.distribute_rewards(bob()));
+
+            assert_eq!(registry.get_pending_rewards(bob(), charlie()), 1500u128);
+            assert_eq!(registry.get_pending_rewards(bob(), dave()), 500u128);
+        }
+
+        #[ink::test]
+        fn deregister_lifecycle_works() {
+            set_caller(alice());
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, bob(), 100, 50);
+            register(&mut registry, charlie(), 100, 50);
+            register(&mut registry, dave(), 100, 50);
+
+            set_caller(bob());
+            set_block(5);
+            assert!(registry.deregister());
+            let profile = registry.get_provider(bob()).unwrap();
+            assert!(!profile.is_active);
+            assert_eq!(profile.stake, 0);
+            assert_eq!(profile.deregister_at, Some(5 + DEFAULT_UNBONDING_PERIOD));
+            assert_eq!(registry.get_unbonding(bob()), vec![UnbondingEntry { amount: 1000, unlock_block: 5 + DEFAULT_UNBONDING_PERIOD }]);
+
+            // Cannot come back or finish early
+            assert!(!registry.set_active(true));
+            assert!(!registry.deregister());
+            set_block(4 + DEFAULT_UNBONDING_PERIOD);
+            assert!(!registry.complete_deregistration());
+
+            set_block(5 + DEFAULT_UNBONDING_PERIOD);
+            assert!(registry.complete_deregistration());
+            assert!(registry.get_provider(bob()).is_none());
+            assert_eq!(registry.get_provider_count(), 2);
+            // The last provider takes Bob's slot
+            assert_eq!(addresses(registry.list_providers(0, 10)), vec![dave(), charlie()]);
+            assert!(registry.withdraw_unbonded());
+
+            // The address can register again
+            set_value(1000);
+            assert!(registry.register_provider("http://again.com".to_string(), 100, 50u128));
+            assert_eq!(addresses(registry.list_providers(0, 10)), vec![dave(), charlie(), bob()]);
+        }
+
+        #[ink::test]
+        fn deregister_with_delegations_fails() {
+            let mut registry = setup();
+            delegate(&mut registry, charlie(), 1000);
+
+            set_caller(bob());
+            assert!(!registry.deregister());
+
+            set_caller(charlie());
+            assert!(registry.undelegate(bob(), 1000));
+            set_caller(bob());
+            assert!(registry.deregister());
+
+            // Charlie's undelegation outlives Bob's profile
+            set_block(DEFAULT_UNBONDING_PERIOD);
+            assert!(registry.complete_deregistration());
+            assert_eq!(registry.get_undelegating(bob(), charlie()), vec![UnbondingEntry { amount: 1000, unlock_block: DEFAULT_UNBONDING_PERIOD }]);
+            set_caller(charlie());
+            assert!(registry.withdraw_undelegated(bob()));
+            assert_eq!(registry.get_delegation_pool(bob()).unbonding, 0);
+        }
+
+        #[ink::test]
+        fn suspend_blocks_activation_and_exit() {
+            let mut registry = setup();
+            assert!(registry.request_unstake(500));
+
+            set_caller(bob());
+            assert!(!registry.suspend(bob()));
+
+            set_caller(alice());
+            assert!(registry.suspend(bob()));
+            assert!(!registry.suspend(bob()));
+            let profile = registry.get_provider(bob()).unwrap();
+            assert!(profile.suspended && !profile.is_active);
+
+            set_caller(bob());
+            assert!(!registry.set_active(true));
+            assert!(!registry.withdraw_stake(500));
+            assert!(!registry.deregister());
+            // Stake that was already unbonding stays put
+            set_block(DEFAULT_UNBONDING_PERIOD);
+            assert!(!registry.withdraw_unbonded());
+
+            set_caller(alice());
+            assert!(registry.unsuspend(bob()));
+            assert!(!registry.get_provider(bob()).unwrap().is_active);
+            set_caller(bob());
+            assert!(registry.withdraw_unbonded());
             assert!(registry.set_active(true));
-            assert_eq!(registry.get_provider(bob()).unwrap().is_active, true);
         }
 
         #[ink::test]
-        fn set_active_not_registered_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            assert!(!registry.set_active(false));
+        fn reserve_and_release_capacity_works() {
+            let mut registry = setup();
+            grant(&mut registry, Role::JobManager, charlie());
+            assert_eq!(registry.available_units(bob()), 100);
+
+            assert!(registry.reserve_capacity(bob(), 60, 1));
+            assert!(registry.reserve_capacity(bob(), 40, 2));
+            assert_eq!(registry.available_units(bob()), 0);
+            assert_eq!(registry.get_reservation(bob(), 1), Some(60));
+            assert_eq!(registry.get_provider(bob()).unwrap().active_jobs, 2);
+
+            assert!(registry.release_capacity(bob(), 1));
+            assert_eq!(registry.available_units(bob()), 60);
+            assert_eq!(registry.get_reservation(bob(), 1), None);
+            assert!(!registry.release_capacity(bob(), 1));
+
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!((profile.reserved_units, profile.active_jobs), (40, 1));
+        }
+
+        #[ink::test]
+        fn reserve_capacity_rejects_overcommit() {
+            let mut registry = setup();
+            grant(&mut registry, Role::JobManager, charlie());
+
+            assert!(registry.reserve_capacity(bob(), 70, 1));
+            assert!(!registry.reserve_capacity(bob(), 31, 2));
+            // Same job cannot reserve twice
+            assert!(!registry.reserve_capacity(bob(), 10, 1));
+            assert!(!registry.reserve_capacity(bob(), 0, 3));
+            assert_eq!(registry.available_units(bob()), 30);
+        }
+
+        #[ink::test]
+        fn reserve_capacity_requires_role_and_active_provider() {
+            let mut registry = setup();
+            grant(&mut registry, Role::JobManager, charlie());
+
+            set_caller(alice());
+            assert!(!registry.reserve_capacity(bob(), 10, 1));
+
+            set_caller(bob());
+            assert!(registry.set_active(false));
+            set_caller(charlie());
+            assert!(!registry.reserve_capacity(bob(), 10, 1));
+            assert_eq!(registry.available_units(bob()), 0);
+        }
+
+        #[ink::test]
+        fn active_jobs_block_deregistration() {
+            let mut registry = setup();
+            grant(&mut registry, Role::JobManager, charlie());
+            assert!(registry.reserve_capacity(bob(), 10, 1));
+
+            set_caller(bob());
+            assert!(!registry.deregister());
+
+            set_caller(charlie());
+            assert!(registry.release_capacity(bob(), 1));
+            set_caller(bob());
+            assert!(registry.deregister());
+        }
+
+        #[ink::test]
+        fn register_provider_defaults_to_hourly_pricing() {
+            let mut registry = setup();
+            assert_eq!(registry.get_provider(bob()).unwrap().pricing, PricingModel::Hourly { rate: 50, min_hours: 0 });
+
+            // Hourly pricing follows update_provider
+            set_caller(bob());
+            assert!(registry.update_provider("http://provider.com".to_string(), 70u128, None));
+            assert_eq!(registry.get_provider(bob()).unwrap().pricing, PricingModel::Hourly { rate: 70, min_hours: 0 });
+        }
+
+        #[ink::test]
+        fn update_provider_replaces_pricing() {
+            let mut registry = setup();
+            set_caller(bob());
+
+            let per_unit = PricingModel::PerUnit { unit: "1k tokens".to_string(), price: 3 };
+            assert!(registry.update_provider("http://provider.com".to_string(), 70u128, Some(per_unit.clone())));
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.pricing, per_unit);
+            assert_eq!(profile.hourly_rate, 70);
+
+            // A rate alone leaves a non-hourly model in place
+            assert!(registry.update_provider("http://provider.com".to_string(), 80u128, None));
+            assert_eq!(registry.get_provider(bob()).unwrap().pricing, per_unit);
+
+            // A new hourly model's rate wins over the rate argument
+            let hourly = PricingModel::Hourly { rate: 90, min_hours: 2 };
+            assert!(registry.update_provider("http://provider.com".to_string(), 80u128, Some(hourly.clone())));
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.pricing, hourly);
+            assert_eq!(profile.hourly_rate, 90);
+        }
+
+        #[ink::test]
+        fn update_provider_rejects_invalid_pricing() {
+            let mut registry = setup();
+            set_caller(bob());
+
+            let invalid = PricingModel::PerUnit { unit: String::new(), price: 3 };
+            assert!(!registry.update_provider("http://moved.com".to_string(), 70u128, Some(invalid)));
+            let profile = registry.get_provider(bob()).unwrap();
+            assert_eq!(profile.endpoint, "http://provider.com");
+            assert_eq!(profile.pricing, PricingModel::Hourly { rate: 50, min_hours: 0 });
+        }
+
+        #[ink::test]
+        fn quote_hourly_rounds_up_and_applies_minimum() {
+            let mut registry = setup();
+            set_caller(bob());
+            assert!(registry.update_pricing(PricingModel::Hourly { rate: 10, min_hours: 2 }));
+            assert_eq!(registry.get_provider(bob()).unwrap().hourly_rate, 10);
+
+            // 4 units * 2 minimum hours * 10
+            assert_eq!(registry.quote(bob(), 4, 60), Some(80));
+            // 3h 1s bills 4 hours
+            assert_eq!(registry.quote(bob(), 4, 3 * 3_600 + 1), Some(160));
+        }
+
+        #[ink::test]
+        fn quote_per_unit_ignores_duration() {
+            let mut registry = setup();
+            set_caller(bob());
+            assert!(registry.update_pricing(PricingModel::PerUnit { unit: "1k-tokens".to_string(), price: 3 }));
+
+            assert_eq!(registry.quote(bob(), 1_000, 0), Some(3_000));
+            assert_eq!(registry.quote(bob(), 1_000, 86_400), Some(3_000));
+            assert_eq!(registry.quote(charlie(), 1, 0), None);
+        }
+
+        #[ink::test]
+        fn quote_tiered_is_graduated() {
+            let mut registry = setup();
+            set_caller(bob());
+            let tiers = vec![
+                PriceTier { up_to: Some(100), price_per_unit: 10 },
+                PriceTier { up_to: Some(1_000), price_per_unit: 5 },
+                PriceTier { up_to: None, price_per_unit: 1 },
+            ];
+            assert!(registry.update_pricing(PricingModel::Tiered(tiers)));
+
+            assert_eq!(registry.quote(bob(), 50, 0), Some(500));
+            assert_eq!(registry.quote(bob(), 300, 0), Some(1_000 + 1_000));
+            assert_eq!(registry.quote(bob(), 2_000, 0), Some(1_000 + 4_500 + 1_000));
+        }
+
+        #[ink::test]
+        fn quote_tiered_beyond_last_band_is_none() {
+            let mut registry = setup();
+            set_caller(bob());
+            assert!(registry.update_pricing(PricingModel::Tiered(vec![PriceTier { up_to: Some(100), price_per_unit: 10 }])));
+
+            assert_eq!(registry.quote(bob(), 100, 0), Some(1_000));
+            assert_eq!(registry.quote(bob(), 101, 0), None);
+        }
+
+        #[ink::test]
+        fn update_pricing_rejects_invalid_models() {
+            let mut registry = setup();
+            set_caller(bob());
+
+            assert!(!registry.update_pricing(PricingModel::PerUnit { unit: String::new(), price: 1 }));
+            assert!(!registry.update_pricing(PricingModel::Tiered(vec![])));
+            assert!(!registry.update_pricing(PricingModel::Tiered(vec![
+                PriceTier { up_to: Some(100), price_per_unit: 10 },
+                PriceTier { up_to: Some(100), price_per_unit: 5 },
+            ])));
+            assert!(!registry.update_pricing(PricingModel::Tiered(vec![
+                PriceTier { up_to: None, price_per_unit: 10 },
+                PriceTier { up_to: Some(100), price_per_unit: 5 },
+            ])));
+            assert_eq!(registry.get_provider(bob()).unwrap().pricing, PricingModel::Hourly { rate: 50, min_hours: 0 });
+
+            set_caller(charlie());
+            assert!(!registry.update_pricing(PricingModel::Hourly { rate: 1, min_hours: 0 }));
+        }
+
+        fn endpoint_key() -> (secp256k1::SecretKey, [u8; 33]) {
+            let secp = secp256k1::Secp256k1::new();
+            let secret = secp256k1::SecretKey::from_slice(&[0x24; 32]).unwrap();
+            (secret, secp256k1::PublicKey::from_secret_key(&secp, &secret).serialize())
+        }
+
+        fn sign_challenge(secret: &secp256k1::SecretKey, challenge: [u8; 32]) -> Vec<u8> {
+            let secp = secp256k1::Secp256k1::new();
+            let message = secp256k1::Message::from_digest_slice(&challenge).unwrap();
+            let (recovery_id, compact) = secp.sign_ecdsa_recoverable(&message, secret).serialize_compact();
+            let mut signature = compact.to_vec();
+            signature.push(recovery_id.to_i32() as u8);
+            signature
+        }
+
+        fn verification_setup() -> (ComputeProviderRegistry, secp256k1::SecretKey) {
+            let mut registry = setup();
+            let (secret, public) = endpoint_key();
+            set_caller(bob());
+            assert!(registry.bind_identity_key(IdentityKey::Ecdsa(public)));
+            grant(&mut registry, Role::Verifier, charlie());
+            (registry, secret)
+        }
+
+        #[ink::test]
+        fn verify_endpoint_challenge_with_ecdsa_works() {
+            let (mut registry, secret) = verification_setup();
+
+            let challenge = registry.issue_challenge(bob()).unwrap();
+            assert_eq!(registry.get_challenge(bob()), Some(challenge));
+            assert!(registry.verify_endpoint_challenge(bob(), sign_challenge(&secret, challenge)));
+
+            assert!(registry.get_provider(bob()).unwrap().endpoint_verified);
+            // The challenge is single-use
+            assert_eq!(registry.get_challenge(bob()), None);
+            assert!(!registry.verify_endpoint_challenge(bob(), sign_challenge(&secret, challenge)));
+        }
+
+        #[ink::test]
+        fn verify_endpoint_challenge_wrong_signer_fails() {
+            let (mut registry, _) = verification_setup();
+
+            let challenge = registry.issue_challenge(bob()).unwrap();
+            let other = secp256k1::SecretKey::from_slice(&[0x25; 32]).unwrap();
+            assert!(!registry.verify_endpoint_challenge(bob(), sign_challenge(&other, challenge)));
+            assert!(!registry.verify_endpoint_challenge(bob(), vec![0u8; 64]));
+            assert!(!registry.get_provider(bob()).unwrap().endpoint_verified);
         }
 
         #[ink::test]
-        fn add_stake_works() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(1000);
-            registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            set_value(500);
-            assert!(registry.add_stake());
-            
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.stake, 1500u128);
+        fn verify_endpoint_challenge_expires() {
+            let (mut registry, secret) = verification_setup();
+
+            let challenge = registry.issue_challenge(bob()).unwrap();
+            set_block(ink::env::block_number::<ink::env::DefaultEnvironment>() + CHALLENGE_VALIDITY + 1);
+            assert!(!registry.verify_endpoint_challenge(bob(), sign_challenge(&secret, challenge)));
         }
 
         #[ink::test]
-        fn add_stake_zero_amount_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
+        fn challenges_require_verifier_and_key() {
+            let (mut registry, _) = verification_setup();
+
             set_caller(bob());
+            assert_eq!(registry.issue_challenge(bob()), None);
+
+            // Dave has no profile, Alice (admin) may issue without the role
+            set_caller(alice());
+            assert_eq!(registry.issue_challenge(dave()), None);
+            assert!(registry.issue_challenge(bob()).is_some());
+
+            set_caller(charlie());
             set_value(1000);
-            registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            set_value(0);
-            assert!(!registry.add_stake());
-            
-            // Stake should remain unchanged
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.stake, 1000u128);
+            assert!(registry.register_provider("http://charlie.com".to_string(), 10, 1u128));
+            assert_eq!(registry.issue_challenge(charlie()), None);
         }
 
         #[ink::test]
-        fn add_stake_not_registered_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
+        fn endpoint_change_resets_verification() {
+            let (mut registry, secret) = verification_setup();
+            let challenge = registry.issue_challenge(bob()).unwrap();
+            assert!(registry.verify_endpoint_challenge(bob(), sign_challenge(&secret, challenge)));
+
+            // Same endpoint keeps the verification
             set_caller(bob());
-            set_value(500);
-            assert!(!registry.add_stake());
+            assert!(registry.update_provider("http://provider.com".to_string(), 60u128, None));
+            assert!(registry.get_provider(bob()).unwrap().endpoint_verified);
+
+            assert!(registry.update_provider("http://moved.com".to_string(), 60u128, None));
+            assert!(!registry.get_provider(bob()).unwrap().endpoint_verified);
         }
 
-        #[ink::test]
-        fn withdraw_stake_by_inactive_provider_works() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(2000);
-            registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            // Set provider inactive
-            registry.set_active(false);
-            
-            // Withdraw partial stake
-            assert!(registry.withdraw_stake(500u128));
-            
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.stake, 1500u128);
+        fn sr25519_endpoint_key() -> schnorrkel::Keypair {
+            schnorrkel::MiniSecretKey::from_bytes(&[0x42; 32])
+                .unwrap()
+                .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519)
+        }
+
+        /// Sign `message` the way `sr25519_verify` expects, in the "substrate" context.
+        fn sign_sr25519(keypair: &schnorrkel::Keypair, message: &[u8]) -> Vec<u8> {
+            keypair.sign(schnorrkel::signing_context(b"substrate").bytes(message)).to_bytes().to_vec()
         }
 
         #[ink::test]
-        fn withdraw_stake_by_admin_as_provider_works() {
+        fn verify_endpoint_challenge_with_sr25519_works() {
+            let mut registry = setup();
+            let keypair = sr25519_endpoint_key();
+            set_caller(bob());
+            assert!(registry.bind_identity_key(IdentityKey::Sr25519(keypair.public.to_bytes())));
+
             set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            // Admin registers as a provider
-            set_value(2000);
-            registry.register_provider(
-                "http://admin-provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            // Admin can withdraw from their own account even if active
-            assert!(registry.withdraw_stake(500u128));
-            
-            let profile = registry.get_provider(alice()).unwrap();
-            assert_eq!(profile.stake, 1500u128);
+            let challenge = registry.issue_challenge(bob()).unwrap();
+            // Another key's signature does not count
+            let other = schnorrkel::MiniSecretKey::from_bytes(&[0x43; 32])
+                .unwrap()
+                .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519);
+            assert!(!registry.verify_endpoint_challenge(bob(), sign_sr25519(&other, &challenge)));
+
+            assert!(registry.verify_endpoint_challenge(bob(), sign_sr25519(&keypair, &challenge)));
+            assert!(registry.get_provider(bob()).unwrap().endpoint_verified);
+            assert_eq!(registry.get_challenge(bob()), None);
         }
 
         #[ink::test]
-        fn withdraw_stake_active_provider_fails() {
+        fn sr25519_key_rejects_malformed_signature() {
+            let mut registry = setup();
+            set_caller(bob());
+            assert!(registry.bind_identity_key(IdentityKey::Sr25519([0x7; 32])));
+
             set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
+            assert!(registry.issue_challenge(bob()).is_some());
+            assert!(!registry.verify_endpoint_challenge(bob(), vec![0u8; 65]));
+            assert!(!registry.verify_endpoint_challenge(bob(), vec![0u8; 64]));
+        }
+
+        fn sla_setup() -> ComputeProviderRegistry {
+            let mut registry = setup();
             set_caller(bob());
-            set_value(2000);
-            registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            // Active provider cannot withdraw stake
-            assert!(!registry.withdraw_stake(500u128));
-            
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.stake, 2000u128);
+            assert!(registry.set_sla_terms(Some(SlaTerms { max_response_blocks: 5, uptime_target_bps: 9_000 })));
+            assert!(registry.bind_identity_key(IdentityKey::Ecdsa(endpoint_key().1)));
+            grant(&mut registry, Role::Monitor, charlie());
+            registry
         }
 
         #[ink::test]
-        fn withdraw_stake_insufficient_balance_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
+        fn set_sla_terms_validates() {
+            let mut registry = setup();
             set_caller(bob());
-            set_value(1000);
-            registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            registry.set_active(false);
-            
-            // Try to withdraw more than staked
-            assert!(!registry.withdraw_stake(1500u128));
-            
+
+            assert!(!registry.set_sla_terms(Some(SlaTerms { max_response_blocks: 0, uptime_target_bps: 9_000 })));
+            assert!(!registry.set_sla_terms(Some(SlaTerms { max_response_blocks: 5, uptime_target_bps: 10_001 })));
+            assert_eq!(registry.get_provider(bob()).unwrap().sla, None);
+
+            let terms = SlaTerms { max_response_blocks: 5, uptime_target_bps: 9_500 };
+            assert!(registry.set_sla_terms(Some(terms)));
+            assert_eq!(registry.get_provider(bob()).unwrap().sla, Some(terms));
+            assert!(registry.set_sla_terms(None));
+            assert_eq!(registry.get_provider(bob()).unwrap().sla, None);
+        }
+
+        /// Probes Bob as the caller in the next block. With `Some(blocks)` Bob
+        /// signs the nonce and the answer is submitted that many blocks later;
+        /// `None` submits the probe unanswered.
+        fn probe(registry: &mut ComputeProviderRegistry, answer_after: Option<u32>) -> bool {
+            let issued_at = ink::env::block_number::<ink::env::DefaultEnvironment>() + 1;
+            set_block(issued_at);
+            let Some(nonce) = registry.issue_probe(bob()) else { return false; };
+            let signature = answer_after.map(|blocks| {
+                set_block(issued_at + blocks);
+                sign_challenge(&endpoint_key().0, nonce)
+            });
+            registry.submit_liveness(bob(), signature)
+        }
+
+        #[ink::test]
+        fn submit_liveness_tracks_rolling_uptime() {
+            let mut registry = sla_setup();
+
+            assert!(probe(&mut registry, Some(2)));
+            assert_eq!(registry.get_provider(bob()).unwrap().uptime_bps, 10_000);
+
+            // Too slow counts as down: 10000 - 10%
+            assert!(probe(&mut registry, Some(6)));
+            assert_eq!(registry.get_provider(bob()).unwrap().uptime_bps, 9_000);
+
+            assert!(probe(&mut registry, Some(5)));
             let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.stake, 1000u128);
+            assert_eq!(profile.uptime_bps, 9_100);
+            assert_eq!(profile.liveness_checks, 3);
         }
 
         #[ink::test]
-        fn set_reputation_by_admin_works() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(1000);
-            registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            set_caller(alice());
-            assert!(registry.set_reputation(bob(), 85));
-            
+        fn liveness_leaves_reputation_alone() {
+            let mut registry = sla_setup();
+
+            assert!(probe(&mut registry, Some(1)));
+            assert!(probe(&mut registry, None));
+            assert!(probe(&mut registry, Some(10)));
             let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.reputation_score, 85);
+            assert_eq!(profile.liveness_checks, 3);
+            assert_eq!(profile.reputation_score, 100);
         }
 
         #[ink::test]
-        fn set_reputation_not_admin_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            set_value(1000);
-            registry.register_provider(
-                "http://provider.com".to_string(),
-                100,
-                50u128
-            );
-            
-            // Non-admin trying to set reputation
+        fn submit_liveness_requires_providers_signature() {
+            let mut registry = sla_setup();
             set_caller(charlie());
-            assert!(!registry.set_reputation(bob(), 85));
-            
-            let profile = registry.get_provider(bob()).unwrap();
-            assert_eq!(profile.reputation_score, 100); // Unchanged
+            let nonce = registry.issue_probe(bob()).unwrap();
+            let issued_at = ink::env::block_number::<ink::env::DefaultEnvironment>();
+            assert_eq!(registry.get_probe(charlie(), bob()), Some((nonce, issued_at)));
+
+            // The monitor cannot answer for Bob, nor with a stale nonce
+            let monitor_key = secp256k1::SecretKey::from_slice(&[0x25; 32]).unwrap();
+            assert!(!registry.submit_liveness(bob(), Some(sign_challenge(&monitor_key, nonce))));
+            assert!(!registry.submit_liveness(bob(), Some(sign_challenge(&endpoint_key().0, [0x1; 32]))));
+            assert!(!registry.submit_liveness(bob(), Some(vec![0u8; 65])));
+            assert_eq!(registry.get_provider(bob()).unwrap().liveness_checks, 0);
+            assert!(registry.get_probe(charlie(), bob()).is_some());
+
+            assert!(registry.submit_liveness(bob(), Some(sign_challenge(&endpoint_key().0, nonce))));
+            assert_eq!(registry.get_probe(charlie(), bob()), None);
+            // The probe is answered once
+            assert!(!registry.submit_liveness(bob(), Some(sign_challenge(&endpoint_key().0, nonce))));
+            assert_eq!(registry.get_provider(bob()).unwrap().liveness_checks, 1);
         }
 
         #[ink::test]
-        fn set_reputation_nonexistent_provider_fails() {
-            set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            assert!(!registry.set_reputation(bob(), 85));
+        fn response_time_counts_from_issue_block() {
+            let mut registry = sla_setup();
+            set_caller(charlie());
+            let issued_at = ink::env::block_number::<ink::env::DefaultEnvironment>();
+            let nonce = registry.issue_probe(bob()).unwrap();
+
+            // However quickly Bob signed, the answer lands after the SLA's 5 blocks
+            set_block(issued_at + 6);
+            assert!(registry.submit_liveness(bob(), Some(sign_challenge(&endpoint_key().0, nonce))));
+            assert_eq!(registry.get_provider(bob()).unwrap().uptime_bps, 0);
         }
 
         #[ink::test]
-        fn set_min_stake_by_admin_works() {
+        fn issue_probe_requires_identity_key() {
             set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            assert!(registry.set_min_stake(2000u128));
-            assert_eq!(registry.get_min_stake(), 2000u128);
+            let mut registry = ComputeProviderRegistry::new(1000);
+            register(&mut registry, dave(), 100, 50);
+            grant(&mut registry, Role::Monitor, charlie());
+
+            assert_eq!(registry.issue_probe(dave()), None);
+            assert!(!registry.submit_liveness(dave(), None));
         }
 
         #[ink::test]
-        fn set_min_stake_not_admin_fails() {
+        fn meets_sla_needs_enough_checks_and_uptime() {
+            let mut registry = sla_setup();
+
+            for _ in 0..MIN_LIVENESS_CHECKS - 1 {
+                assert!(probe(&mut registry, Some(1)));
+            }
+            assert!(!registry.meets_sla(bob()));
+            assert!(probe(&mut registry, Some(1)));
+            assert!(registry.meets_sla(bob()));
+
+            assert!(probe(&mut registry, None));
+            assert!(probe(&mut registry, None));
+            assert!(!registry.meets_sla(bob()));
+
+            // Without declared terms there is nothing to meet
+            assert!(!registry.meets_sla(dave()));
+        }
+
+        #[ink::test]
+        fn issue_probe_once_per_block() {
+            let mut registry = sla_setup();
+            grant(&mut registry, Role::Monitor, dave());
+
+            set_caller(charlie());
+            let nonce = registry.issue_probe(bob()).unwrap();
+            // Not while unanswered, nor again in the same block
+            assert_eq!(registry.issue_probe(bob()), None);
+            assert!(registry.submit_liveness(bob(), Some(sign_challenge(&endpoint_key().0, nonce))));
+            assert_eq!(registry.issue_probe(bob()), None);
+            // Other monitors are counted separately
+            set_caller(dave());
+            assert!(registry.issue_probe(bob()).is_some());
+            assert!(registry.submit_liveness(bob(), None));
+            assert_eq!(registry.get_provider(bob()).unwrap().liveness_checks, 2);
+
+            set_caller(charlie());
+            assert!(probe(&mut registry, Some(1)));
+        }
+
+        #[ink::test]
+        fn liveness_not_monitor_fails() {
+            let mut registry = sla_setup();
+
             set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            set_caller(bob());
-            assert!(!registry.set_min_stake(2000u128));
-            assert_eq!(registry.get_min_stake(), 1000u128);
+            assert_eq!(registry.issue_probe(bob()), None);
+            assert!(!registry.submit_liveness(bob(), None));
+            assert_eq!(registry.get_provider(bob()).unwrap().liveness_checks, 0);
         }
 
         #[ink::test]
-        fn get_provider_nonexistent() {
+        fn change_waits_for_delay() {
             set_caller(alice());
-            let min_stake = 1000u128;
-            let registry = ComputeProviderRegistry::new(min_stake);
-            
-            assert!(registry.get_provider(bob()).is_none());
+            set_block(100);
+            let mut registry = ComputeProviderRegistry::new(1000);
+
+            let id = registry.propose_change(ParamChange::MinStake(5000)).unwrap();
+            assert_eq!(
+                registry.get_pending_change(id),
+                Some(PendingChange { change: ParamChange::MinStake(5000), executable_at: 100 + GOVERNANCE_DELAY })
+            );
+
+            set_block(100 + GOVERNANCE_DELAY - 1);
+            assert!(!registry.execute_change(id));
+            assert_eq!(registry.get_min_stake(), 1000);
+
+            // Anyone may execute once the delay has passed, but only once
+            set_caller(bob());
+            set_block(100 + GOVERNANCE_DELAY);
+            assert!(registry.execute_change(id));
+            assert_eq!(registry.get_min_stake(), 5000);
+            assert_eq!(registry.get_pending_change(id), None);
+            assert!(!registry.execute_change(id));
         }
 
         #[ink::test]
-        fn multiple_providers_registration() {
+        fn cancel_change_works() {
             set_caller(alice());
-            let min_stake = 1000u128;
-            let mut registry = ComputeProviderRegistry::new(min_stake);
-            
-            // Register first provider
+            let mut registry = ComputeProviderRegistry::new(1000);
+            let first = registry.propose_change(ParamChange::MinStake(5000)).unwrap();
+            let second = registry.propose_change(ParamChange::GrantRole(Role::Slasher, charlie())).unwrap();
+            assert_ne!(first, second);
+
             set_caller(bob());
-            set_value(1000);
-            assert!(registry.register_provider(
-                "http://bob.com".to_string(),
-                100,
-                50u128
-            ));
-            
-            // Register second provider
-            set_caller(charlie());
-            set_value(1500);
-            assert!(registry.register_provider(
-                "http://charlie.com".to_string(),
-                200,
-                75u128
-            ));
-            
-            assert_eq!(registry.get_provider_count(), 2);
-            
-            let bob_profile = registry.get_provider(bob()).unwrap();
-            let charlie_profile = registry.get_provider(charlie()).unwrap();
-            
-            assert_eq!(bob_profile.endpoint, "http://bob.com");
-            assert_eq!(bob_profile.stake, 1000u128);
-            assert_eq!(charlie_profile.endpoint, "http://charlie.com");
-            assert_eq!(charlie_profile.stake, 1500u128);
+            assert!(!registry.cancel_change(first));
+
+            set_caller(alice());
+            assert!(registry.cancel_change(first));
+            assert!(!registry.cancel_change(first));
+
+            set_block(GOVERNANCE_DELAY);
+            assert!(!registry.execute_change(first));
+            assert!(registry.execute_change(second));
+            assert_eq!(registry.get_min_stake(), 1000);
+            assert!(registry.has_role(Role::Slasher, charlie()));
         }
     }
 }
@@ -0,0 +1,56 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+//! Stand-in for `ComputeProviderRegistry` in the `payment_escrow` end-to-end
+//! tests. It records every `slash` call and, while accepting, pays the slashed
+//! amount to the beneficiary from its own balance, so tests don't have to wait
+//! out the registry's governance delay to grant the escrow the slasher role.
+
+pub use self::mock_registry::{MockRegistry, MockRegistryRef};
+
+#[ink::contract]
+mod mock_registry {
+    use ink::prelude::vec::Vec;
+    use ink::primitives::{H160, U256};
+
+    /// Arguments of one `slash` call: provider, amount, reason code, beneficiary
+    pub type SlashCall = (H160, u128, u8, Option<H160>);
+
+    #[ink(storage)]
+    pub struct MockRegistry {
+        accept: bool,
+        slashes: Vec<SlashCall>,
+    }
+
+    impl MockRegistry {
+        /// Funds the mock with the transferred value, which backs the payouts.
+        #[ink(constructor, payable)]
+        pub fn new(accept: bool) -> Self {
+            Self { accept, slashes: Vec::new() }
+        }
+
+        /// Whether later `slash` calls succeed.
+        #[ink(message)]
+        pub fn set_accept(&mut self, accept: bool) {
+            self.accept = accept;
+        }
+
+        #[ink(message)]
+        pub fn slash(&mut self, provider: H160, amount: u128, reason_code: u8, beneficiary: Option<H160>) -> bool {
+            self.slashes.push((provider, amount, reason_code, beneficiary));
+            if !self.accept {
+                return false;
+            }
+            if let Some(to) = beneficiary {
+                if self.env().transfer(to, U256::from(amount)).is_err() {
+                    return false;
+                }
+            }
+            true
+        }
+
+        #[ink(message)]
+        pub fn get_slashes(&self) -> Vec<SlashCall> {
+            self.slashes.clone()
+        }
+    }
+}
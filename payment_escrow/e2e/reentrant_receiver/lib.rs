@@ -0,0 +1,123 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+//! Hostile counterparty for the `payment_escrow` end-to-end tests. It owns
+//! jobs, funds escrows and claims payouts like any other account, and when
+//! armed it calls back into the escrow while the escrow is paying it.
+//!
+//! Receiving plain transfers needs the wildcard selector, and a contract with
+//! a wildcard may only have one other message, so everything else goes
+//! through `run`.
+
+pub use self::reentrant_receiver::{Action, EscrowCall, ReentrantReceiver, ReentrantReceiverRef};
+
+#[ink::contract]
+mod reentrant_receiver {
+    use ink::env::call::{build_call, ExecutionInput, Selector};
+    use ink::env::DefaultEnvironment;
+    use ink::prelude::string::String;
+    use ink::primitives::H160;
+
+    /// An escrow message returning `bool`, with its job id argument if it
+    /// takes one
+    #[derive(ink::scale::Encode, ink::scale::Decode, Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "std",
+        derive(ink::scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct EscrowCall {
+        pub selector: [u8; 4],
+        pub job_id: Option<u128>,
+    }
+
+    #[derive(ink::scale::Encode, ink::scale::Decode, Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "std", derive(ink::scale_info::TypeInfo))]
+    pub enum Action {
+        /// Make `call` the next time the escrow pays this contract
+        Arm(EscrowCall),
+        /// Make `call` now
+        Call(EscrowCall),
+        /// Submit a job to the queue with the transferred value as budget
+        SubmitJob { deadline: u32 },
+        /// Fund the escrow for `job_id` with the transferred value
+        Deposit { job_id: u128, provider: H160 },
+        /// Report the result of the armed call
+        Reentry,
+    }
+
+    #[ink(storage)]
+    pub struct ReentrantReceiver {
+        escrow: H160,
+        queue: H160,
+        armed: Option<EscrowCall>,
+        reentry: Option<bool>,
+    }
+
+    impl ReentrantReceiver {
+        #[ink(constructor)]
+        pub fn new(escrow: H160, queue: H160) -> Self {
+            Self { escrow, queue, armed: None, reentry: None }
+        }
+
+        /// Performs `action` and returns its result. `Arm` returns `None`, and
+        /// `Reentry` returns `None` until a payment has triggered the armed call.
+        #[ink(message, payable, selector = @)]
+        pub fn run(&mut self, action: Action) -> Option<bool> {
+            let value = self.env().transferred_value();
+            match action {
+                Action::Arm(call) => {
+                    self.armed = Some(call);
+                    self.reentry = None;
+                    None
+                }
+                Action::Call(call) => Some(self.call_escrow(call)),
+                Action::SubmitJob { deadline } => {
+                    let submitted = build_call::<DefaultEnvironment>()
+                        .call(self.queue)
+                        .transferred_value(value)
+                        .exec_input(
+                            ExecutionInput::new(Selector::new(ink::selector_bytes!("submit_job")))
+                                .push_arg(String::from("model"))
+                                .push_arg(String::from("data"))
+                                .push_arg(deadline)
+                                .push_arg(false),
+                        )
+                        .returns::<u128>()
+                        .try_invoke();
+                    Some(matches!(submitted, Ok(Ok(_))))
+                }
+                Action::Deposit { job_id, provider } => {
+                    let deposited = build_call::<DefaultEnvironment>()
+                        .call(self.escrow)
+                        .transferred_value(value)
+                        .exec_input(
+                            ExecutionInput::new(Selector::new(ink::selector_bytes!("deposit_for_job")))
+                                .push_arg(job_id)
+                                .push_arg(provider),
+                        )
+                        .returns::<bool>()
+                        .try_invoke();
+                    Some(matches!(deposited, Ok(Ok(true))))
+                }
+                Action::Reentry => self.reentry,
+            }
+        }
+
+        /// Receives the escrow's payouts and makes the armed call, once.
+        #[ink(message, payable, selector = _)]
+        pub fn receive(&mut self) {
+            if let Some(call) = self.armed.take() {
+                self.reentry = Some(self.call_escrow(call));
+            }
+        }
+
+        fn call_escrow(&self, call: EscrowCall) -> bool {
+            let input = ExecutionInput::new(Selector::new(call.selector));
+            let builder = build_call::<DefaultEnvironment>().call(self.escrow);
+            let result = match call.job_id {
+                Some(job_id) => builder.exec_input(input.push_arg(job_id)).returns::<bool>().try_invoke(),
+                None => builder.exec_input(input).returns::<bool>().try_invoke(),
+            };
+            matches!(result, Ok(Ok(true)))
+        }
+    }
+}